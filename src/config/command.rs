@@ -1,22 +1,114 @@
-extern crate serde_json;
-
+use super::{guard, Context, Module, Outcome, Status};
 use crate::logger::Logger;
 use crate::shell;
+use schemars::JsonSchema;
+use serde::{de, Deserialize, Deserializer};
+use serde_json::Value;
 use std::io::Write;
 
-// TODO: Handle possible command execution errors.
+/// The arguments of a command task: the command to run in the shell, either on
+/// its own or along with guards that tell whether it has to be run.
+#[derive(Clone, Debug, JsonSchema)]
+#[schemars(rename = "CommandArgs", untagged)]
+pub enum Args {
+    /// The command to run.
+    Command(String),
+
+    /// The command to run, with guards.
+    Guarded(Guarded),
+}
+
+/// The arguments of a command task that has guards. The command is only run
+/// if none of the guards are met.
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[schemars(rename = "GuardedCommandArgs")]
+#[serde(deny_unknown_fields)]
+pub struct Guarded {
+    /// The command to run.
+    pub command: String,
+
+    /// A file that the command creates. The command isn't run if it exists.
+    pub creates: Option<String>,
+
+    /// A command (run with `sh -c`). The command isn't run if it succeeds.
+    pub unless: Option<String>,
+}
+
+impl Args {
+    /// Returns the command to run.
+    pub fn command(&self) -> &str {
+        match self {
+            Args::Command(c) => c,
+            Args::Guarded(g) => &g.command,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Args {
+    // NOTE: This isn't derived with `#[serde(untagged)]` so that mistakes in the
+    // guarded form are reported as they are (e.g. an unknown field) instead of
+    // as not matching any form.
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Value::deserialize(deserializer)? {
+            Value::String(c) => Ok(Args::Command(c)),
+            value @ Value::Object(_) => Guarded::deserialize(value)
+                .map(Args::Guarded)
+                .map_err(de::Error::custom),
+            _ => Err(de::Error::custom(
+                "expected a command, or a map with a `command` and guards",
+            )),
+        }
+    }
+}
+
+/// Returns a boolean indicating whether the guards of a command task are met.
+/// Commands without guards always have to be run, as there is no telling what
+/// they do.
+fn check(args: &Args, context: &Context) -> bool {
+    match args {
+        Args::Command(_) => false,
+        Args::Guarded(g) => guard::is_met(g.creates.as_deref(), g.unless.as_deref(), context),
+    }
+}
+
 /// Runs a command in the shell.
-pub fn run<W>(args: &serde_json::Value, logger: &mut Logger<W>) -> Option<()>
+///
+/// The command's output goes straight to the terminal (so that it can prompt
+/// for input), which is why it isn't captured.
+fn run<W>(args: &Args, _context: &Context, logger: &mut Logger<W>) -> Outcome
 where
     W: Write,
 {
-    if let Some(c) = &args.as_str() {
-        logger.info(&format!("Running command: {}", c));
-        shell::run(c);
-        logger.success("Done.");
-        Some(())
-    } else {
-        logger.warn("Nothing to do.");
-        Some(())
+    let c = args.command();
+    logger.info(&format!("Running command: {}", c));
+    match shell::run(c) {
+        Ok(status) if status.success() => Status::Changed.into(),
+        Ok(status) => Status::Failed(format!("The command failed ({}).", status)).into(),
+        Err(e) => Status::Failed(format!("Failed to run the command: {}", e)).into(),
+    }
+}
+
+/// Runs commands in the shell.
+pub struct Command;
+
+impl Module for Command {
+    type Args = Args;
+
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn check(&self, args: &Self::Args, context: &Context) -> bool {
+        check(args, context)
+    }
+
+    fn apply<W>(&self, args: &Self::Args, context: &Context, logger: &mut Logger<W>) -> Outcome
+    where
+        W: Write,
+    {
+        run(args, context, logger)
     }
 }
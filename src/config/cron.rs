@@ -1,92 +1,130 @@
 //! Module for working with cron jobs.
 //! **WARNING: This module is highly experimental and should be used with caution.**
 extern crate cron_rs;
-extern crate serde_json;
 extern crate tempfile;
 
+use super::{Context, Module, Outcome, Status};
 use crate::logger::Logger;
 use crate::shell;
 
 use cron_rs::Scheduler;
+use schemars::JsonSchema;
+use serde::Deserialize;
 use std::env;
 use std::fs::File;
 use std::io::Write;
 
+/// The arguments of a cron task.
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[schemars(rename = "CronArgs")]
+#[serde(deny_unknown_fields)]
+pub struct Args {
+    /// The cron expression of when to run the job (e.g. `0 * * * *`).
+    pub intervals: String,
+
+    /// The command to run.
+    pub job: String,
+}
+
+/// Returns the problems with a cron task that would stop it from running.
+fn validate(args: &Args, _context: &Context) -> Vec<String> {
+    if Scheduler::new(&args.intervals).is_ok() {
+        Vec::new()
+    } else {
+        vec![format!(
+            "Invalid cron interval expression `{}`.",
+            args.intervals
+        )]
+    }
+}
+
+/// Returns the line of a cron job in the crontab.
+fn entry(args: &Args) -> String {
+    format!("{} {}", args.intervals, args.job)
+}
+
+/// Returns the current user's crontab, which is empty if they don't have one
+/// yet.
+fn crontab() -> String {
+    // NOTE: `crontab -l` fails if the user doesn't have a crontab.
+    shell::output("crontab", Some(&vec!["-l"]))
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+        .unwrap_or_default()
+}
+
+/// Returns a boolean indicating whether a cron job is already in the current
+/// user's crontab.
+fn check(args: &Args, _context: &Context) -> bool {
+    let entry = entry(args);
+    crontab().lines().any(|line| line.trim() == entry)
+}
+
 // TODO: add support for job description comments.
 // TODO: test possible failure scenarios.
-// TODO: handle `crontab` command failure.
 // TODO: handle cron daemon not installed or not running.
-// TODO: handle duplicate entries.
 /// Adds a cron job for the current user.
-pub fn add<W>(args: &serde_json::Value, logger: &mut Logger<W>) -> Option<()>
+fn add<W>(args: &Args, _context: &Context, logger: &mut Logger<W>) -> Outcome
 where
     W: Write,
 {
-    if let Some(cron) = &args.as_object() {
-        logger.info("Adding cron job.");
-
-        // Parse and validate specified cron intervals expression.
-        let intervals = match cron.get("intervals") {
-            Some(intervals) => match intervals.as_str() {
-                Some(intervals) => intervals,
-                None => {
-                    logger.error("Intervals expression must be a string.");
-                    return None;
-                }
-            },
-            None => {
-                logger.error("No intervals specified.");
-                return None;
-            }
-        };
+    logger.info("Adding cron job.");
+    let Args { intervals, .. } = args;
 
-        // Parse and validate specified cron job.
-        let job = match cron.get("job") {
-            Some(job) => match job.as_str() {
-                Some(job) => job,
-                None => {
-                    logger.error("Job must be a string.");
-                    return None;
-                }
-            },
-            None => {
-                logger.error("No job specified.");
-                return None;
-            }
+    // Validate cron entry and install.
+    // ? Could I possibly make the following code less nauseating to look at?
+    if let Ok(_) = Scheduler::new(intervals) {
+        // TODO: check if there's a better way to do this.
+        let tmpdir = match env::var("TMPDIR") {
+            Ok(tmpdir) => tmpdir,
+            Err(_) => String::from("/tmp"),
         };
+        let tmpdir = tempfile::tempdir_in(tmpdir).unwrap();
+        let tmpfile = tmpdir.path().join("dotman.cron");
+
+        // Get existing crontab entries and append to it.
+        let mut existing = crontab();
+        if !existing.is_empty() && !existing.ends_with('\n') {
+            existing.push('\n');
+        }
+        let mut file = File::create(&tmpfile).unwrap();
+        file.write_all(existing.as_bytes()).unwrap();
+        file.write_fmt(format_args!("{}\n", entry(args))).unwrap();
 
-        // Validate cron entry and install.
-        // ? Could I possibly make the following code less nauseating to look at?
-        if let Ok(_) = Scheduler::new(intervals) {
-            // TODO: check if there's a better way to do this.
-            let tmpdir = match env::var("TMPDIR") {
-                Ok(tmpdir) => tmpdir,
-                Err(_) => String::from("/tmp"),
-            };
-            let tmpdir = tempfile::tempdir_in(tmpdir).unwrap();
-            let tmpfile = tmpdir.path().join("dotman.cron");
-
-            // Get existing crontab entries and append to it.
-            let crontab = shell::output("crontab", Some(&vec!["-l"]));
-            let cron_out = String::from_utf8(crontab.unwrap().stdout).unwrap();
-            let mut file = File::create(&tmpfile).unwrap();
-            file.write_fmt(format_args!("{}\n", cron_out)).unwrap();
-            file.write_fmt(format_args!("{} {}\n", intervals, job))
-                .unwrap();
-
-            // Install cron entry.
-            shell::run(&format!("crontab -- {}", &tmpfile.display()));
-            logger.success("Done.");
-
-            Some(())
-        } else {
-            logger.error("Invalid cron interval expression.");
-
-            None
+        // Install cron entry.
+        match shell::run(&format!("crontab -- {}", &tmpfile.display())) {
+            Ok(status) if status.success() => Status::Changed.into(),
+            Ok(status) => Status::Failed(format!("crontab failed ({}).", status)).into(),
+            Err(e) => Status::Failed(format!("Failed to run crontab: {}", e)).into(),
         }
     } else {
-        logger.warn("Nothing to do.");
+        Status::Failed(String::from("Invalid cron interval expression.")).into()
+    }
+}
+
+/// Adds cron jobs for the current user.
+pub struct Cron;
+
+impl Module for Cron {
+    type Args = Args;
+
+    fn name(&self) -> &'static str {
+        "cron"
+    }
+
+    fn validate(&self, args: &Self::Args, context: &Context) -> Vec<String> {
+        validate(args, context)
+    }
+
+    fn check(&self, args: &Self::Args, context: &Context) -> bool {
+        check(args, context)
+    }
 
-        None
+    fn apply<W>(&self, args: &Self::Args, context: &Context, logger: &mut Logger<W>) -> Outcome
+    where
+        W: Write,
+    {
+        add(args, context, logger)
     }
 }
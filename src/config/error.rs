@@ -0,0 +1,148 @@
+//! Errors that can occur while loading a config file.
+use std::{
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Represents an error in a config file.
+///
+/// # Fields
+///
+/// * `path` - The config file.
+/// * `line` - The line that the error is on (starting at 1), if known.
+/// * `column` - The column that the error is at (starting at 1), if known.
+/// * `message` - What went wrong.
+/// * `snippet` - The offending line of the config, if known.
+/// * `hint` - A suggestion on how to fix the error, if there is one.
+#[derive(Clone, Debug)]
+pub struct ConfigError {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+    pub snippet: Option<String>,
+    pub hint: Option<String>,
+}
+
+impl ConfigError {
+    /// Creates an error that isn't tied to a location in the config.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The config file.
+    /// * `message` - What went wrong.
+    pub fn new(path: &Path, message: &str) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            line: None,
+            column: None,
+            message: message.to_string(),
+            snippet: None,
+            hint: hint(message),
+        }
+    }
+
+    /// Creates an error at a location in the config.
+    ///
+    /// The location is stripped from the message if the message ends with it
+    /// (as the messages of the JSON, YAML and TOML parsers do).
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The config file.
+    /// * `contents` - The contents of the config file.
+    /// * `message` - What went wrong.
+    /// * `line` - The line that the error is on (starting at 1).
+    /// * `column` - The column that the error is at (starting at 1).
+    pub fn at(path: &Path, contents: &str, message: &str, line: usize, column: usize) -> Self {
+        let message = match message.rfind(" at line ") {
+            Some(i) => &message[..i],
+            None => message,
+        };
+
+        Self {
+            line: Some(line),
+            column: Some(column),
+            snippet: contents
+                .lines()
+                .nth(line.saturating_sub(1))
+                .map(str::to_string),
+            ..Self::new(path, message)
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, ":{}:{}", line, column)?;
+        }
+        write!(f, ": {}", self.message)?;
+
+        // Point at the offending column, the way compilers do.
+        if let (Some(snippet), Some(line), Some(column)) = (&self.snippet, self.line, self.column) {
+            let gutter = " ".repeat(line.to_string().len());
+            let offset: String = snippet
+                .chars()
+                .take(column.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+
+            write!(f, "\n{} |", gutter)?;
+            write!(f, "\n{} | {}", line, snippet)?;
+            write!(f, "\n{} | {}^", gutter, offset)?;
+        }
+
+        if let Some(hint) = &self.hint {
+            write!(f, "\nhint: {}", hint)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for ConfigError {}
+
+/// Returns a suggestion on how to fix an error, based on its message.
+fn hint(message: &str) -> Option<String> {
+    let hint = if message.contains("No such file or directory") {
+        String::from("Check the path of the config file, or pass one with --config.")
+    } else if message.contains("trailing comma") {
+        String::from("JSON doesn't allow a comma after the last item of a list or object.")
+    } else if message.contains("EOF while parsing") || message.contains("unexpected eof") {
+        String::from("Check for an unclosed bracket, brace or quote.")
+    } else if let Some(field) = missing_field(message) {
+        if field == "name" || field == "module" {
+            format!(
+                "Add a `{}` to the task. Every task needs a `name` and a `module`.",
+                field
+            )
+        } else {
+            format!(
+                "Add the `{}` key. `dotman schema` lists every required key.",
+                field
+            )
+        }
+    } else if message.contains("unknown field") {
+        String::from("Check the spelling of the key. `dotman schema` lists every allowed key.")
+    } else if message.contains("unknown variant") {
+        String::from("Check the spelling of the value against the expected ones.")
+    } else if message.contains("invalid type") {
+        String::from("Check that the value is quoted (or not) as required.")
+    } else {
+        return None;
+    };
+
+    Some(hint)
+}
+
+/// Returns the name of the field that a message says is missing, if it says so
+/// (e.g. "missing field `module`").
+fn missing_field(message: &str) -> Option<&str> {
+    let start = message.find("missing field `")? + "missing field `".len();
+    let len = message[start..].find('`')?;
+
+    Some(&message[start..start + len])
+}
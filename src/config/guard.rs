@@ -0,0 +1,26 @@
+//! Guards that tell whether a command or script task has to be run.
+use super::Context;
+use crate::shell;
+use shellexpand::tilde;
+
+/// Returns a boolean indicating whether any of a task's guards are met, which
+/// means that the task doesn't have to be run. Tasks without guards always have
+/// to be run.
+///
+/// # Arguments
+///
+/// * `creates` - A file that the task creates, which is met if the file
+///   exists. Relative paths are resolved against the root of the dotfiles
+///   repository.
+/// * `unless` - A command (run with `sh -c`), which is met if it succeeds.
+/// * `context` - The environment that the task is run in.
+pub fn is_met(creates: Option<&str>, unless: Option<&str>, context: &Context) -> bool {
+    let created = creates.is_some_and(|file| context.root.join(tilde(file).as_ref()).exists());
+
+    // NOTE: The command's output is captured so that it doesn't clutter the
+    // task's output.
+    created
+        || unless.is_some_and(|cmd| {
+            shell::output("sh", Some(&vec!["-c", cmd])).is_ok_and(|o| o.status.success())
+        })
+}
@@ -0,0 +1,139 @@
+//! Module for deploying dotfiles into the home directory.
+
+use super::{Context, Module, Outcome, Status};
+use crate::dotfiles::link::{Linker, Mode, Strategy};
+use crate::dotfiles::manifest::Manifest;
+use crate::logger::Logger;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use shellexpand::tilde;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// The arguments of a link task. Every field is optional.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[schemars(rename = "LinkArgs")]
+#[serde(deny_unknown_fields)]
+pub struct Args {
+    /// The package directories (relative to the repository root) to link.
+    /// Defaults to linking the whole repository.
+    pub packages: Option<Vec<PathBuf>>,
+
+    /// The directory to link into. Defaults to the home directory.
+    pub target: Option<String>,
+
+    /// Whether missing directories are linked as a whole. Defaults to `true`.
+    pub fold: Option<bool>,
+
+    /// What to do with existing files. Defaults to the config-wide strategy.
+    pub conflict: Option<Strategy>,
+
+    /// How to deploy dotfiles. Defaults to `symlink`.
+    pub mode: Option<Mode>,
+
+    /// Paths (relative to the package) mapped to the mode to deploy them
+    /// with, overriding `mode`.
+    #[serde(default)]
+    pub modes: HashMap<PathBuf, Mode>,
+}
+
+/// Returns the problems with a link task that would stop it from running.
+fn validate(args: &Args, context: &Context) -> Vec<String> {
+    args.packages
+        .iter()
+        .flatten()
+        .map(|pkg| context.root.join(pkg))
+        .filter(|pkg| !pkg.is_dir())
+        .map(|pkg| format!("Package {} does not exist.", pkg.display()))
+        .collect()
+}
+
+/// Symlinks dotfiles from the repository into a target directory.
+///
+/// See `Args` for the arguments.
+fn run<W>(args: &Args, context: &Context, logger: &mut Logger<W>) -> Outcome
+where
+    W: Write,
+{
+    let target = PathBuf::from(tilde(args.target.as_deref().unwrap_or("~")).as_ref());
+    let packages = match &args.packages {
+        Some(packages) => packages.iter().map(|pkg| context.root.join(pkg)).collect(),
+        None => vec![context.root.clone()],
+    };
+
+    let manifest = match Manifest::load() {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            return Status::Failed(format!("Failed to load the deployment manifest: {}", e)).into()
+        }
+    };
+
+    let mut linker = Linker::new(&context.root, &target, manifest);
+    linker.conflict = context.conflict;
+    linker.facts = context.facts.clone();
+    linker.variables = context.variables.clone();
+    linker.diff = context.diff;
+
+    linker.modes = args.modes.clone();
+    if let Some(fold) = args.fold {
+        linker.fold = fold;
+    }
+    if let Some(mode) = args.mode {
+        linker.mode = mode;
+    }
+    if let Some(conflict) = args.conflict {
+        linker.conflict = conflict;
+    }
+
+    let mut result = Ok(());
+
+    for package in packages.iter() {
+        logger.info(&format!(
+            "Linking {} into {}",
+            package.display(),
+            target.display()
+        ));
+
+        if let Err(e) = linker.link(package, logger) {
+            result = Err(format!("Failed to link {}: {}", package.display(), e));
+            break;
+        }
+    }
+
+    // Whatever was deployed before a failure still has to be recorded.
+    if let Err(e) = linker.manifest.save() {
+        return Status::Failed(format!("Failed to save the deployment manifest: {}", e)).into();
+    }
+
+    match result {
+        // Nothing was deployed because of existing files in the way.
+        Ok(_) if linker.changes == 0 && linker.skipped > 0 => Status::Skipped.into(),
+        Ok(_) if linker.changes == 0 => Status::Ok.into(),
+        Ok(_) => Status::Changed.into(),
+        Err(e) => Status::Failed(e).into(),
+    }
+}
+
+/// Deploys dotfiles into a target directory. All of its arguments are optional,
+/// so they may be left out.
+pub struct Link;
+
+impl Module for Link {
+    type Args = Option<Args>;
+
+    fn name(&self) -> &'static str {
+        "link"
+    }
+
+    fn validate(&self, args: &Self::Args, context: &Context) -> Vec<String> {
+        validate(&args.clone().unwrap_or_default(), context)
+    }
+
+    fn apply<W>(&self, args: &Self::Args, context: &Context, logger: &mut Logger<W>) -> Outcome
+    where
+        W: Write,
+    {
+        run(&args.clone().unwrap_or_default(), context, logger)
+    }
+}
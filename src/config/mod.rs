@@ -1,133 +1,635 @@
 //! Module for parsing dotman config files.
 extern crate serde_json;
+extern crate serde_yaml;
+extern crate toml;
 
-use crate::display;
-use crate::logger::Logger;
+use crate::consts::{CONFIG_FILES, ENCRYPTED_VALUE_KEY};
+use crate::crypt;
+use crate::dotfiles::link::Strategy;
+use crate::facts::Facts;
 
-use serde::Deserialize;
-use serde_json::Value;
+use schemars::{gen::SchemaGenerator, schema::RootSchema, JsonSchema};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Map, Value};
 use std::{
-    collections::HashMap,
     fs,
-    io::{BufReader, Write},
-    path::Path,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
 };
 
+mod error;
+mod guard;
+mod module;
+mod outcome;
+pub use self::error::ConfigError;
+pub use self::module::{Module, Registry};
+pub use self::outcome::{Outcome, Status};
+
 // Available configuration modules.
 mod command;
 mod cron;
+mod link;
 mod package;
 mod script;
+mod template;
 
-/// Represents a module's handler function.
-type ModuleHandler<T> = fn(&Value, &mut Logger<T>) -> Option<()>;
-
-/// Represents a dotman task to perform.
+/// Represents the environment that tasks are run in.
 ///
 /// # Fields
 ///
-/// * `name` - The name of the task (will be displayed in the task banner).
-/// * `module` - The module to use for running the task.
-/// * `args` - The arguments to be passed to the module.
-#[derive(Clone, Debug, Deserialize)]
+/// * `root` - The root directory of the dotfiles repository. Relative paths in
+///   task arguments are resolved against it.
+/// * `conflict` - The default strategy for handling existing files when
+///   deploying dotfiles. Tasks may override it.
+/// * `facts` - The facts about the system that the tasks are run on.
+/// * `variables` - The variables that templates are rendered with.
+/// * `diff` - Whether to print a diff before replacing copied or templated
+///   files.
+#[derive(Clone, Debug, Default)]
+pub struct Context {
+    pub root: PathBuf,
+    pub conflict: Strategy,
+    pub facts: Facts,
+    pub variables: Map<String, Value>,
+    pub diff: bool,
+}
+
+impl Context {
+    /// Creates the environment to run the tasks of a config in, gathering the
+    /// facts about the system.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The root directory of the dotfiles repository.
+    /// * `config` - The config that the tasks are defined in.
+    pub fn new(root: &Path, config: &Config) -> Self {
+        let mut facts = Facts::gather();
+        facts.class = config.class.clone();
+
+        Self {
+            root: root.to_path_buf(),
+            conflict: config.conflict,
+            facts,
+            variables: config.variables.clone(),
+            diff: false,
+        }
+    }
+}
+
+/// Represents a dotman task to perform.
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
 pub struct Task {
+    /// The name of the task (will be displayed in the task banner).
     pub name: String,
+
+    /// The module to use for running the task.
     pub module: String,
+
+    /// The arguments to be passed to the module.
+    #[serde(default)]
     pub args: Value,
 }
 
+/// Represents the format of a config file.
+///
+/// # Variants
+///
+/// * `Json` - JSON (`.json`).
+/// * `Yaml` - YAML (`.yaml` or `.yml`).
+/// * `Toml` - TOML (`.toml`).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    #[default]
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    /// Returns the format of a config file based on its extension, if it is a
+    /// known one.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the config file.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            "toml" => Ok(Format::Toml),
+            _ => Err(format!(
+                "Unknown config format {} (expected json, yaml or toml).",
+                s
+            )),
+        }
+    }
+}
+
+/// Represents how many tasks had each outcome.
+///
+/// # Fields
+///
+/// * `total_tasks` - The number of tasks.
+/// * `ok_tasks` - The number of tasks whose state was already met.
+/// * `changed_tasks` - The number of tasks that changed the system.
+/// * `skipped_tasks` - The number of tasks that were not run.
+/// * `failed_tasks` - The number of tasks that failed.
 #[derive(Debug, Default)]
 pub struct Stats {
     pub total_tasks: usize,
+    pub ok_tasks: usize,
+    pub changed_tasks: usize,
+    pub skipped_tasks: usize,
     pub failed_tasks: usize,
-    pub success_tasks: usize,
+}
+
+impl Stats {
+    /// Counts the outcomes of tasks.
+    ///
+    /// # Arguments
+    ///
+    /// * `outcomes` - The outcomes of the tasks.
+    pub fn new(outcomes: &[Outcome]) -> Self {
+        let mut stats = Self {
+            total_tasks: outcomes.len(),
+            ..Default::default()
+        };
+
+        for outcome in outcomes.iter() {
+            match outcome.status {
+                Status::Ok => stats.ok_tasks += 1,
+                Status::Changed => stats.changed_tasks += 1,
+                Status::Skipped => stats.skipped_tasks += 1,
+                Status::Failed(_) => stats.failed_tasks += 1,
+            }
+        }
+
+        stats
+    }
 }
 
 /// Represents a dotman configuration.
-///
-/// # Fields
-///
-/// * `tasks` - The list (vector) of tasks that dotman has to perform.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct Config {
+    /// The tasks to perform, in order.
     tasks: Option<Vec<Task>>,
 
+    /// The default strategy for handling existing files when deploying
+    /// dotfiles.
+    #[serde(default)]
+    pub conflict: Strategy,
+
+    /// The class of the system (e.g. `work`), used to select alternate
+    /// dotfiles.
+    pub class: Option<String>,
+
+    /// The variables that templates are rendered with.
+    #[serde(default)]
+    pub variables: Map<String, Value>,
+
+    /// Regular expressions matching text that must never be displayed (e.g.
+    /// `--token[= ](\S+)`). See `Logger::add_pattern`.
+    #[serde(default)]
+    pub redact: Vec<String>,
+
+    /// The decrypted values of the encrypted values in the config. These must
+    /// never be displayed.
     #[serde(skip_deserializing)]
-    pub stats: Stats,
+    #[schemars(skip)]
+    pub secrets: Vec<String>,
 }
 
 impl Config {
-    /// Runs the list of tasks defined in a configuration.
+    /// Returns the tasks defined in a configuration, in order.
+    pub fn tasks(&self) -> &[Task] {
+        self.tasks.as_deref().unwrap_or_default()
+    }
+
+    /// Checks every task in a configuration without running any of them, and
+    /// returns all the problems found (e.g. unknown modules, invalid arguments
+    /// or missing scripts).
     ///
     /// # Arguments
     ///
-    /// * `logger` - The logger to write task output to.
+    /// * `registry` - The modules that the tasks can use.
+    /// * `context` - The environment that the tasks would be run in.
     ///
     /// # Examples
     ///
-    /// ```
-    /// let file = "/home/johndoe/config.json";
-    /// let mut logger = logger::Logger::new();
-    /// let config = config::parse(&file);
+    /// ```no_run
+    /// use dotman::config;
+    ///
+    /// let context = config::Context {
+    ///     root: std::path::PathBuf::from("/home/johndoe/dotfiles"),
+    ///     ..Default::default()
+    /// };
+    /// let registry = config::Registry::<std::io::Stdout>::default();
+    /// let config = config::parse("/home/johndoe/dotfiles/dotman.json", None).unwrap();
     ///
-    /// config.run_tasks(&mut logger);
+    /// for problem in config.check(&registry, &context) {
+    ///     eprintln!("{}", problem);
+    /// }
     /// ```
-    pub fn run_tasks<W>(&mut self, logger: &mut Logger<W>)
+    pub fn check<W>(&self, registry: &Registry<W>, context: &Context) -> Vec<String>
     where
         W: Write,
     {
-        let mut module_dispatcher: HashMap<String, ModuleHandler<_>> = HashMap::new();
-        let tasks: &Vec<Task>;
-        if let Some(t) = &self.tasks {
-            tasks = t;
-        } else {
-            logger.warn("No tasks specified.");
-
-            // We return early when there are no tasks to run.
-            return;
+        let mut problems = Vec::new();
+
+        for (i, task) in self.tasks().iter().enumerate() {
+            // Tasks are numbered so that unnamed or duplicate names can be told apart.
+            let name = format!("Task {} ({})", i + 1, task.name);
+
+            problems.extend(
+                registry
+                    .validate(&task.module, &task.args, context)
+                    .into_iter()
+                    .map(|problem| format!("{}: {}", name, problem)),
+            );
         }
 
-        // We use a hashmap to map each module with its handler function.
-        module_dispatcher.insert(String::from("command"), command::run);
-        module_dispatcher.insert(String::from("package"), package::install);
-        module_dispatcher.insert(String::from("script"), script::run);
-        module_dispatcher.insert(String::from("cron"), cron::add);
-
-        // Iterate through and run each task.
-        for task in tasks.iter() {
-            display::banner(&format!("TASK: {}", &task.name), None, None);
-            match module_dispatcher[&task.module](&task.args, logger) {
-                Some(_) => self.stats.success_tasks += 1,
-                None => self.stats.failed_tasks += 1,
+        problems
+    }
+}
+
+/// Looks for a config file in a dotfiles repository and returns its path.
+///
+/// The locations in `consts::CONFIG_FILES` are tried in order and the first
+/// file that exists is returned.
+///
+/// # Arguments
+///
+/// * `root` - The root directory of the dotfiles repository.
+///
+/// # Examples
+///
+/// ```
+/// use dotman::config;
+///
+/// let root = std::path::PathBuf::from("/home/johndoe/dotfiles");
+///
+/// if let Some(file) = config::find(&root) {
+///     println!("Found config at {}", file.display());
+/// }
+/// ```
+pub fn find(root: &Path) -> Option<PathBuf> {
+    CONFIG_FILES
+        .iter()
+        .map(|file| root.join(file))
+        .find(|file| file.is_file())
+}
+
+/// Returns the JSON Schema of the config file, which editors can use to
+/// validate configs and suggest completions.
+///
+/// Any string in the config can be an encrypted value instead (see
+/// `crypt::decrypt_values`), except for those with a fixed set of values.
+///
+/// # Arguments
+///
+/// * `registry` - The modules that tasks can use.
+///
+/// # Examples
+///
+/// ```
+/// use dotman::config;
+///
+/// let registry = config::Registry::<std::io::Stdout>::default();
+/// let schema = config::schema(&registry);
+/// println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+/// ```
+pub fn schema<W>(registry: &Registry<W>) -> RootSchema
+where
+    W: Write,
+{
+    let mut gen = SchemaGenerator::default();
+    let task = registry.task_schema(&mut gen);
+
+    // The derived schema of a task allows any module and args, so it is
+    // replaced with one that ties each module to its args.
+    let mut schema = gen.into_root_schema_for::<Config>();
+    schema.definitions.insert(String::from("Task"), task);
+
+    let mut schema = serde_json::to_value(schema).unwrap();
+    allow_encrypted(&mut schema);
+    schema["definitions"]["EncryptedValue"] = json!({
+        "description": "A value encrypted with `dotman encrypt --string`, which is decrypted when the config is loaded.",
+        "type": "object",
+        "required": [ENCRYPTED_VALUE_KEY],
+        "properties": {
+            ENCRYPTED_VALUE_KEY: { "type": "string" },
+        },
+        "additionalProperties": false,
+    });
+
+    serde_json::from_value(schema).unwrap()
+}
+
+/// Lets every string in a schema be an encrypted value instead, unless it has
+/// a fixed set of values (e.g. a conflict strategy).
+fn allow_encrypted(schema: &mut Value) {
+    match schema {
+        Value::Object(map) => {
+            let is_string = match map.get("type") {
+                Some(Value::String(t)) => t == "string",
+                Some(Value::Array(types)) => types.iter().any(|t| t == "string"),
+                _ => false,
             };
-            println!();
+
+            if !is_string || map.contains_key("enum") {
+                map.values_mut().for_each(allow_encrypted);
+                return;
+            }
+
+            // The description is kept outside, where editors look for it.
+            let mut string = std::mem::take(map);
+            for key in ["title", "description", "default"].iter() {
+                if let Some(value) = string.remove(*key) {
+                    map.insert(key.to_string(), value);
+                }
+            }
+            map.insert(
+                String::from("anyOf"),
+                json!([string, { "$ref": "#/definitions/EncryptedValue" }]),
+            );
         }
+
+        Value::Array(values) => values.iter_mut().for_each(allow_encrypted),
+
+        _ => (),
     }
 }
 
-/// Parses and returns a JSON configuration.
+/// Parses and returns a configuration.
+///
+/// Errors point at the offending location in the file where possible. The
+/// arguments of tasks that use a built-in module are checked to have the shape
+/// that the module expects too (see `Registry::check_args`), and errors in them
+/// name the task they are in (e.g. `tasks[2].args`).
+///
+/// Encrypted values (see `crypt::decrypt_values`) are decrypted with the key in
+/// the key file, after the rest of the config has been checked, so that errors
+/// never quote a decrypted value.
 ///
 /// # Arguments
 ///
 /// * `file` - The path to the configuration file.
+/// * `format` - The format of the file. If not specified, it is determined
+///   from the file's extension (JSON if the extension is unknown).
 ///
 /// # Examples
 ///
-/// ```
-/// let file = "/home/johndoe/config.json";
-/// let config = config::parse(&file);
+/// ```no_run
+/// use dotman::config::{self, Format};
+///
+/// match config::parse("/home/johndoe/dotman.yaml", None) {
+///     Ok(config) => println!("{:#?}", config),
+///     Err(e) => eprintln!("{}", e),
+/// }
+///
+/// let config = config::parse("/home/johndoe/dotman.conf", Some(Format::Toml)).unwrap();
 /// println!("{:#?}", config);
 /// ```
-pub fn parse<P: AsRef<Path>>(file: P) -> Config {
-    let file = fs::File::open(&file).unwrap();
-    let reader = BufReader::new(file);
-    let mut config: Config = serde_json::from_reader(reader).unwrap();
-    config.stats.total_tasks = 1;
-
-    match &config.tasks {
-        Some(tasks) => config.stats.total_tasks += tasks.iter().count(),
-        None => (),
+pub fn parse<P: AsRef<Path>>(file: P, format: Option<Format>) -> Result<Config, Box<ConfigError>> {
+    let path = file.as_ref();
+    let format = format
+        .or_else(|| Format::from_path(path))
+        .unwrap_or_default();
+    let contents =
+        fs::read_to_string(path).map_err(|e| Box::new(ConfigError::new(path, &e.to_string())))?;
+
+    // Every format is read into a JSON value first, so that the tasks' args
+    // look the same to the modules regardless of the format.
+    let mut value: Value = deserialize(path, &contents, format)?;
+
+    // Errors quote the values that they are about, so the config is checked
+    // before anything is decrypted, with `****` in place of encrypted values.
+    let mut masked = value.clone();
+    crypt::mask_values(&mut masked);
+    check_shape(path, &contents, format, masked)?;
+
+    let mut secrets = Vec::new();
+    if let Err(e) = crypt::decrypt_values(&mut value, &mut secrets) {
+        return Err(Box::new(ConfigError {
+            hint: Some(format!(
+                "Make sure that the key at {} is the one the values were encrypted with.",
+                crypt::key_file().display()
+            )),
+            ..ConfigError::new(path, &format!("Failed to decrypt a value: {}", e))
+        }));
+    }
+
+    // NOTE: The error isn't included, as it may quote a decrypted value.
+    let mut config: Config = serde_json::from_value(value).map_err(|_| {
+        Box::new(ConfigError::new(
+            path,
+            "A decrypted value doesn't have the type expected where it is used",
+        ))
+    })?;
+    config.secrets = secrets;
+
+    Ok(config)
+}
+
+/// Checks that a config has the expected shape, including the arguments of
+/// tasks that use a built-in module.
+///
+/// # Arguments
+///
+/// * `path` - The config file.
+/// * `contents` - The contents of the config file.
+/// * `format` - The format of the config file.
+/// * `value` - The config, read into a JSON value.
+fn check_shape(
+    path: &Path,
+    contents: &str,
+    format: Format,
+    value: Value,
+) -> Result<(), Box<ConfigError>> {
+    let config: Config = match serde_json::from_value(value) {
+        Ok(config) => config,
+
+        // The value doesn't know where anything came from in the file, so the
+        // contents are deserialized again to find out where the error is.
+        Err(e) => {
+            return Err(match deserialize::<Config>(path, contents, format) {
+                Err(located) => located,
+                Ok(_) => Box::new(ConfigError::new(path, &e.to_string())),
+            })
+        }
     };
 
-    config
+    // The modules only deserialize their arguments when a task is checked or
+    // run, so the arguments' shape is checked here, while loading. Modules that
+    // library users register themselves are only checked by `Config::check`.
+    let registry = Registry::<io::Sink>::default();
+    for (i, task) in config.tasks().iter().enumerate() {
+        if let Err(e) = registry.check_args(&task.module, &task.args) {
+            return Err(Box::new(ConfigError::new(
+                path,
+                &format!("tasks[{}].args ({}): {}", i, task.name, e),
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Deserializes the contents of a config file in the specified format.
+fn deserialize<T>(path: &Path, contents: &str, format: Format) -> Result<T, Box<ConfigError>>
+where
+    T: DeserializeOwned,
+{
+    let result = match format {
+        Format::Json => serde_json::from_str(contents).map_err(|e| match e.line() {
+            0 => ConfigError::new(path, &e.to_string()),
+            line => ConfigError::at(path, contents, &e.to_string(), line, e.column()),
+        }),
+
+        Format::Yaml => serde_yaml::from_str(contents).map_err(|e| match e.location() {
+            Some(l) => ConfigError::at(path, contents, &e.to_string(), l.line(), l.column()),
+            None => ConfigError::new(path, &e.to_string()),
+        }),
+
+        // NOTE: TOML locations start at 0.
+        Format::Toml => toml::from_str(contents).map_err(|e| match e.line_col() {
+            Some((line, column)) => {
+                ConfigError::at(path, contents, &e.to_string(), line + 1, column + 1)
+            }
+            None => ConfigError::new(path, &e.to_string()),
+        }),
+    };
+
+    result.map_err(Box::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    /// Returns a boolean indicating whether a value is valid against a schema.
+    /// Only the keywords that the config's schema uses are supported.
+    fn is_valid(value: &Value, schema: &Value, root: &Value) -> bool {
+        let all = |key: &str| schema[key].as_array().cloned().unwrap_or_default();
+
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/definitions/");
+            return is_valid(value, &root["definitions"][name], root);
+        }
+
+        let types: Vec<&str> = match &schema["type"] {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        let type_matches = types.is_empty()
+            || types.iter().any(|t| match *t {
+                "string" => value.is_string(),
+                "array" => value.is_array(),
+                "object" => value.is_object(),
+                "boolean" => value.is_boolean(),
+                "integer" => value.is_u64() || value.is_i64(),
+                "number" => value.is_number(),
+                "null" => value.is_null(),
+                _ => false,
+            });
+
+        let properties_match = match value.as_object() {
+            Some(map) => {
+                all("required")
+                    .iter()
+                    .all(|k| map.contains_key(k.as_str().unwrap()))
+                    && map.iter().all(|(k, v)| match &schema["properties"][k] {
+                        Value::Null => match &schema["additionalProperties"] {
+                            Value::Bool(allowed) => *allowed,
+                            Value::Null => true,
+                            additional => is_valid(v, additional, root),
+                        },
+                        property => is_valid(v, property, root),
+                    })
+            }
+            None => true,
+        };
+        let items_match = match (value.as_array(), schema.get("items")) {
+            (Some(items), Some(item)) => items.iter().all(|v| is_valid(v, item, root)),
+            _ => true,
+        };
+
+        type_matches
+            && properties_match
+            && items_match
+            && schema
+                .get("enum")
+                .map_or(true, |e| e.as_array().unwrap().contains(value))
+            && all("allOf").iter().all(|s| is_valid(value, s, root))
+            && (schema.get("anyOf").is_none()
+                || all("anyOf").iter().any(|s| is_valid(value, s, root)))
+            && (schema.get("oneOf").is_none()
+                || all("oneOf")
+                    .iter()
+                    .filter(|s| is_valid(value, s, root))
+                    .count()
+                    == 1)
+    }
+
+    #[test]
+    fn schema_allows_encrypted_values() {
+        let schema = serde_json::to_value(schema(&Registry::<io::Sink>::default())).unwrap();
+        let encrypted = json!({ "$encrypted": "-----BEGIN AGE ENCRYPTED FILE-----..." });
+
+        let config = json!({
+            "class": encrypted,
+            "variables": { "token": encrypted },
+            "tasks": [
+                { "name": "packages", "module": "package", "args": ["vim", encrypted] },
+                { "name": "login", "module": "command", "args": encrypted },
+                { "name": "job", "module": "cron", "args": { "intervals": "@daily", "job": encrypted } },
+            ],
+        });
+        assert!(is_valid(&config, &schema, &schema));
+
+        // Only strings can be encrypted, and only on their own.
+        let config =
+            json!({ "tasks": [{ "name": "packages", "module": "package", "args": encrypted }] });
+        assert!(!is_valid(&config, &schema, &schema));
+        let config = json!({ "tasks": [{ "name": "login", "module": "command", "args": { "$encrypted": "...", "unless": "true" } }] });
+        assert!(!is_valid(&config, &schema, &schema));
+        let config = json!({ "conflict": encrypted });
+        assert!(!is_valid(&config, &schema, &schema));
+    }
+
+    #[test]
+    fn malformed_encrypted_args_are_not_revealed() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // NOTE: The key file is looked for in the home directory.
+        env::set_var("HOME", dir.path());
+        let key = crypt::generate_key(&crypt::key_file()).unwrap();
+        let encrypted = crypt::encrypt(&key.to_public(), b"hunter2secret").unwrap();
+
+        // The packages have to be a list, not a (decrypted) string.
+        let file = dir.path().join("dotman.json");
+        let config = serde_json::json!({
+            "tasks": [{
+                "name": "pkgs",
+                "module": "package",
+                "args": { "$encrypted": encrypted },
+            }],
+        });
+        fs::write(&file, config.to_string()).unwrap();
+
+        let error = parse(&file, None).unwrap_err().to_string();
+        assert!(error.contains("tasks[0].args (pkgs)"), "{}", error);
+        assert!(!error.contains("hunter2secret"), "{}", error);
+    }
 }
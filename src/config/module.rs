@@ -0,0 +1,343 @@
+//! The interface that task modules implement, and the registry that tasks look
+//! their modules up in.
+extern crate serde_json;
+
+use super::{Context, Outcome, Status};
+use crate::logger::Logger;
+
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::{collections::BTreeMap, io::Write, time::Instant};
+
+/// A module that tasks can use (e.g. `command`).
+///
+/// # Examples
+///
+/// A module that greets someone:
+/// ```
+/// use dotman::config::{Context, Module, Outcome, Status};
+/// use dotman::logger::Logger;
+/// use std::io::Write;
+///
+/// #[derive(serde::Deserialize, schemars::JsonSchema)]
+/// struct Args {
+///     name: String,
+/// }
+///
+/// struct Greet;
+///
+/// impl Module for Greet {
+///     type Args = Args;
+///
+///     fn name(&self) -> &'static str {
+///         "greet"
+///     }
+///
+///     fn apply<W: Write>(&self, args: &Args, _: &Context, logger: &mut Logger<W>) -> Outcome {
+///         logger.info(&format!("Hello, {}!", args.name));
+///         Outcome::from(Status::Changed)
+///     }
+/// }
+/// ```
+pub trait Module {
+    /// The arguments that tasks pass to the module. Tasks whose arguments
+    /// don't deserialize into this type are rejected before anything is run.
+    type Args: DeserializeOwned + JsonSchema;
+
+    /// Returns the name that tasks refer to the module by.
+    fn name(&self) -> &'static str;
+
+    /// Returns the JSON Schema of the module's arguments.
+    ///
+    /// # Arguments
+    ///
+    /// * `gen` - The generator of the config schema that the arguments are a
+    ///   part of.
+    fn schema(&self, gen: &mut SchemaGenerator) -> Schema {
+        gen.subschema_for::<Self::Args>()
+    }
+
+    /// Returns the problems with a task's arguments that would stop it from
+    /// running (e.g. a missing script), beyond their shape. There are none by
+    /// default.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The task's arguments.
+    /// * `context` - The environment that the task would be run in.
+    fn validate(&self, _args: &Self::Args, _context: &Context) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Returns whether the state that a task describes is already met, so that
+    /// it doesn't have to be applied. Tasks are always applied by default.
+    ///
+    /// It is called once per task when planning, before any task is applied,
+    /// and again right before applying a task that it said was met (in case an
+    /// earlier task undid its state), so it must not change anything.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The task's arguments.
+    /// * `context` - The environment that the task is run in.
+    fn check(&self, _args: &Self::Args, _context: &Context) -> bool {
+        false
+    }
+
+    /// Runs a task and returns what it did.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The task's arguments.
+    /// * `context` - The environment to run the task in.
+    /// * `logger` - The logger to write the task's output to.
+    fn apply<W>(&self, args: &Self::Args, context: &Context, logger: &mut Logger<W>) -> Outcome
+    where
+        W: Write;
+}
+
+/// A module with its arguments type erased, so that modules with different
+/// arguments can be kept in the same registry.
+trait AnyModule<W>
+where
+    W: Write,
+{
+    fn schema(&self, gen: &mut SchemaGenerator) -> Schema;
+    fn check_args(&self, args: &Value) -> Result<(), String>;
+    fn validate(&self, args: &Value, context: &Context) -> Vec<String>;
+    fn check(&self, args: &Value, context: &Context) -> bool;
+    fn run(&self, args: &Value, context: &Context, logger: &mut Logger<W>) -> Outcome;
+}
+
+impl<M, W> AnyModule<W> for M
+where
+    M: Module,
+    W: Write,
+{
+    fn schema(&self, gen: &mut SchemaGenerator) -> Schema {
+        Module::schema(self, gen)
+    }
+
+    fn check_args(&self, args: &Value) -> Result<(), String> {
+        M::Args::deserialize(args)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    fn validate(&self, args: &Value, context: &Context) -> Vec<String> {
+        match M::Args::deserialize(args) {
+            Ok(args) => Module::validate(self, &args, context),
+            Err(e) => vec![format!("Invalid args: {}", e)],
+        }
+    }
+
+    fn check(&self, args: &Value, context: &Context) -> bool {
+        match M::Args::deserialize(args) {
+            Ok(args) => Module::check(self, &args, context),
+            Err(_) => false,
+        }
+    }
+
+    fn run(&self, args: &Value, context: &Context, logger: &mut Logger<W>) -> Outcome {
+        let args = match M::Args::deserialize(args) {
+            Ok(args) => args,
+            Err(e) => return Status::Failed(format!("Invalid args: {}", e)).into(),
+        };
+
+        self.apply(&args, context, logger)
+    }
+}
+
+/// The modules that tasks can use, by name.
+///
+/// `Registry::default()` contains the built-in modules, and more can be added
+/// with `register`.
+pub struct Registry<W>
+where
+    W: Write,
+{
+    modules: BTreeMap<&'static str, Box<dyn AnyModule<W>>>,
+}
+
+impl<W> Registry<W>
+where
+    W: Write,
+{
+    /// Creates a registry without any modules.
+    pub fn new() -> Self {
+        Self {
+            modules: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a module to the registry, replacing any module with the same name.
+    ///
+    /// # Arguments
+    ///
+    /// * `module` - The module to add.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dotman::config::{Context, Module, Outcome, Registry, Status};
+    /// use dotman::logger::Logger;
+    /// use std::io::{Stdout, Write};
+    ///
+    /// struct Greet;
+    ///
+    /// impl Module for Greet {
+    ///     type Args = String;
+    ///
+    ///     fn name(&self) -> &'static str {
+    ///         "greet"
+    ///     }
+    ///
+    ///     fn apply<W: Write>(&self, name: &String, _: &Context, logger: &mut Logger<W>) -> Outcome {
+    ///         logger.info(&format!("Hello, {}!", name));
+    ///         Outcome::from(Status::Changed)
+    ///     }
+    /// }
+    ///
+    /// let mut registry = Registry::<Stdout>::default();
+    /// registry.register(Greet);
+    /// assert!(registry.names().contains(&"greet"));
+    /// ```
+    pub fn register<M>(&mut self, module: M)
+    where
+        M: Module + 'static,
+    {
+        self.modules.insert(module.name(), Box::new(module));
+    }
+
+    /// Returns the names of the modules in the registry, in alphabetical order.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.modules.keys().copied().collect()
+    }
+
+    /// Returns why the arguments of a task don't have the shape that its module
+    /// expects, if they don't. Nothing else about the task is checked (see
+    /// `validate`), and tasks with an unknown module are let through.
+    ///
+    /// # Arguments
+    ///
+    /// * `module` - The name of the module.
+    /// * `args` - The task's arguments.
+    pub fn check_args(&self, module: &str, args: &Value) -> Result<(), String> {
+        match self.modules.get(module) {
+            Some(m) => m.check_args(args),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the problems with the arguments of a task that uses a module,
+    /// including the module not existing.
+    ///
+    /// # Arguments
+    ///
+    /// * `module` - The name of the module.
+    /// * `args` - The task's arguments.
+    /// * `context` - The environment that the task would be run in.
+    pub fn validate(&self, module: &str, args: &Value, context: &Context) -> Vec<String> {
+        match self.modules.get(module) {
+            Some(m) => m.validate(args, context),
+            None => vec![format!(
+                "Unknown module `{}` (expected one of {}).",
+                module,
+                self.names().join(", ")
+            )],
+        }
+    }
+
+    /// Returns whether the state that a task describes is already met (see
+    /// `Module::check`). Tasks with an unknown module or invalid arguments are
+    /// never met.
+    ///
+    /// # Arguments
+    ///
+    /// * `module` - The name of the module.
+    /// * `args` - The task's arguments.
+    /// * `context` - The environment that the task would be run in.
+    pub fn check(&self, module: &str, args: &Value, context: &Context) -> bool {
+        self.modules
+            .get(module)
+            .is_some_and(|m| m.check(args, context))
+    }
+
+    /// Runs a task that uses a module and returns what it did along with how
+    /// long it took. Whether the task has to be run at all is up to the caller
+    /// (see `check`).
+    ///
+    /// # Arguments
+    ///
+    /// * `module` - The name of the module.
+    /// * `args` - The task's arguments.
+    /// * `context` - The environment to run the task in.
+    /// * `logger` - The logger to write the task's output to.
+    pub fn run(
+        &self,
+        module: &str,
+        args: &Value,
+        context: &Context,
+        logger: &mut Logger<W>,
+    ) -> Outcome {
+        let start = Instant::now();
+        let mut outcome = match self.modules.get(module) {
+            Some(m) => m.run(args, context, logger),
+            None => Status::Failed(format!("Unknown module `{}`.", module)).into(),
+        };
+
+        outcome.duration = Some(start.elapsed());
+        outcome
+    }
+
+    /// Returns the JSON Schema of a task, which allows any module in the
+    /// registry along with its arguments.
+    ///
+    /// # Arguments
+    ///
+    /// * `gen` - The generator of the config schema that tasks are a part of.
+    pub fn task_schema(&self, gen: &mut SchemaGenerator) -> Schema {
+        let tasks: Vec<Value> = self
+            .modules
+            .iter()
+            .map(|(name, module)| {
+                json!({
+                    "type": "object",
+                    "required": ["name", "module"],
+                    "properties": {
+                        "name": {
+                            "description": "The name of the task (will be displayed in the task banner).",
+                            "type": "string",
+                        },
+                        "module": { "enum": [name] },
+                        "args": module.schema(gen),
+                    },
+                })
+            })
+            .collect();
+
+        serde_json::from_value(json!({
+            "description": "A task to perform.",
+            "oneOf": tasks,
+        }))
+        .unwrap()
+    }
+}
+
+impl<W> Default for Registry<W>
+where
+    W: Write,
+{
+    /// Creates a registry with the built-in modules.
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register(super::command::Command);
+        registry.register(super::cron::Cron);
+        registry.register(super::link::Link);
+        registry.register(super::package::Package);
+        registry.register(super::script::Script);
+        registry.register(super::template::Template);
+        registry
+    }
+}
@@ -0,0 +1,66 @@
+//! The results of running tasks.
+use std::time::Duration;
+
+/// Represents what running a task did.
+///
+/// # Variants
+///
+/// * `Ok` - The state that the task describes was already met, so nothing was
+///   changed.
+/// * `Changed` - The task changed the system.
+/// * `Skipped` - The task left the state it describes unmet on purpose (e.g.
+///   every dotfile was in the way of an existing file that was kept).
+/// * `Failed` - The task failed, with the reason why.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Changed,
+    Skipped,
+    Failed(String),
+}
+
+/// Represents how running a task went.
+///
+/// # Fields
+///
+/// * `status` - What running the task did.
+/// * `output` - The output of the task, if it was captured (e.g. the output of
+///   a script).
+/// * `duration` - How long the task took to run, if it was timed.
+///
+/// # Examples
+///
+/// ```
+/// use dotman::config::{Outcome, Status};
+///
+/// let outcome = Outcome {
+///     output: Some(String::from("Hello world\n")),
+///     ..Outcome::from(Status::Changed)
+/// };
+///
+/// assert!(!outcome.is_failed());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub status: Status,
+    pub output: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+impl Outcome {
+    /// Returns a boolean indicating whether the task failed.
+    pub fn is_failed(&self) -> bool {
+        matches!(self.status, Status::Failed(_))
+    }
+}
+
+impl From<Status> for Outcome {
+    /// Creates an outcome without any output or duration.
+    fn from(status: Status) -> Self {
+        Self {
+            status,
+            output: None,
+            duration: None,
+        }
+    }
+}
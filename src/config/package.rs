@@ -1,49 +1,101 @@
 //! Module for performing package management tasks.
 //! **WARNING: This module is highly experimental and should be used with caution.**
-extern crate serde_json;
 
+use super::{Context, Module, Outcome, Status};
 use crate::logger::Logger;
 use packagekit::PackageKit;
+use schemars::JsonSchema;
+use serde::Deserialize;
 use std::io::Write;
 
+/// The arguments of a package task: the names of the packages to install.
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[schemars(rename = "PackageArgs")]
+pub struct Args(pub Vec<String>);
+
+/// Returns a boolean indicating whether a package is installed, going by its
+/// PackageKit ID (`name;version;arch;data`), whose data is `installed` (or
+/// `installed:<repo>`) for installed packages.
+fn is_installed(id: &str) -> bool {
+    id.split(';')
+        .nth(3)
+        .is_some_and(|data| data.starts_with("installed"))
+}
+
+/// Returns a boolean indicating whether all the packages are already installed.
+fn check(args: &Args, _context: &Context) -> bool {
+    let Args(packages) = args;
+    let pk = PackageKit::new();
+
+    // NOTE: Only the first matching package is considered, like in `install`.
+    packages.iter().all(|pkg| {
+        pk.search_package(pkg).is_ok_and(|results| {
+            results
+                .first()
+                .is_some_and(|p| is_installed(&p.id().to_string()))
+        })
+    })
+}
+
 // TODO: add support for specifying package versions.
-/// Installs a list of packages onto the system.
-pub fn install<W>(args: &serde_json::Value, logger: &mut Logger<W>) -> Option<()>
+/// Installs a list of packages onto the system, skipping those that are
+/// already installed.
+fn install<W>(args: &Args, _context: &Context, logger: &mut Logger<W>) -> Outcome
 where
     W: Write,
 {
-    if let Some(packages) = &args.as_array() {
-        let pk = PackageKit::new();
-
-        for pkg in packages.iter() {
-            if let Some(s) = pkg.as_str() {
-                // NOTE: Only the first matching package will be installed.
-                // ? Maybe this isn't the best thing to do?
-                let results = match pk.search_package(s) {
-                    Ok(r) => r,
-                    Err(e) => {
-                        logger.error(&e.to_string());
-                        return None;
-                    }
-                };
-
-                logger.info(&format!("Installing package: {}", results[0].id()));
-                match pk.install(&results[0]) {
-                    Ok(_) => continue,
-                    Err(e) => {
-                        logger.error(&e.to_string());
-                        return None;
-                    }
-                }
-            } else {
-                logger.error("Invalid package name in list of packages.");
-                return None;
-            }
-        }
-        logger.success("Done.");
-        Some(())
-    } else {
+    let Args(packages) = args;
+    if packages.is_empty() {
         logger.warn("Nothing to do.");
-        Some(())
+        return Status::Ok.into();
+    }
+
+    let pk = PackageKit::new();
+
+    for pkg in packages.iter() {
+        // NOTE: Only the first matching package will be installed.
+        // ? Maybe this isn't the best thing to do?
+        let results = match pk.search_package(pkg) {
+            Ok(r) => r,
+            Err(e) => return Status::Failed(e.to_string()).into(),
+        };
+        let package = match results.first() {
+            Some(package) => package,
+            None => return Status::Failed(format!("No package found: {}", pkg)).into(),
+        };
+
+        if is_installed(&package.id().to_string()) {
+            logger.info(&format!("Already installed: {}", package.id()));
+            continue;
+        }
+
+        logger.info(&format!("Installing package: {}", package.id()));
+        if let Err(e) = pk.install(package) {
+            return Status::Failed(e.to_string()).into();
+        }
+    }
+
+    Status::Changed.into()
+}
+
+/// Installs packages onto the system.
+pub struct Package;
+
+impl Module for Package {
+    type Args = Args;
+
+    fn name(&self) -> &'static str {
+        "package"
+    }
+
+    fn check(&self, args: &Self::Args, context: &Context) -> bool {
+        check(args, context)
+    }
+
+    fn apply<W>(&self, args: &Self::Args, context: &Context, logger: &mut Logger<W>) -> Outcome
+    where
+        W: Write,
+    {
+        install(args, context, logger)
     }
 }
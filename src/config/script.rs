@@ -1,27 +1,143 @@
 //! Module for performing script related tasks.
-extern crate serde_json;
-
+use super::{guard, Context, Module, Outcome, Status};
 use crate::logger::Logger;
 use crate::shell;
+use schemars::JsonSchema;
+use serde::{de, Deserialize, Deserializer};
+use serde_json::Value;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// The arguments of a script task: the script to run, relative to the root
+/// of the dotfiles repository, either on its own or along with guards that
+/// tell whether it has to be run.
+#[derive(Clone, Debug, JsonSchema)]
+#[schemars(rename = "ScriptArgs", untagged)]
+pub enum Args {
+    /// The script to run.
+    Script(PathBuf),
+
+    /// The script to run, with guards.
+    Guarded(Guarded),
+}
+
+/// The arguments of a script task that has guards. The script is only run if
+/// none of the guards are met.
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[schemars(rename = "GuardedScriptArgs")]
+#[serde(deny_unknown_fields)]
+pub struct Guarded {
+    /// The script to run, relative to the repository root.
+    pub script: PathBuf,
+
+    /// A file that the script creates. The script isn't run if it exists.
+    pub creates: Option<String>,
+
+    /// A command (run with `sh -c`). The script isn't run if it succeeds.
+    pub unless: Option<String>,
+}
+
+impl Args {
+    /// Returns the script to run, relative to the repository root.
+    pub fn script(&self) -> &Path {
+        match self {
+            Args::Script(s) => s,
+            Args::Guarded(g) => &g.script,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Args {
+    // NOTE: See `command::Args` for why this isn't derived.
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Value::deserialize(deserializer)? {
+            Value::String(s) => Ok(Args::Script(PathBuf::from(s))),
+            value @ Value::Object(_) => Guarded::deserialize(value)
+                .map(Args::Guarded)
+                .map_err(de::Error::custom),
+            _ => Err(de::Error::custom(
+                "expected a script, or a map with a `script` and guards",
+            )),
+        }
+    }
+}
+
+/// Returns the problems with a script task that would stop it from running.
+fn validate(args: &Args, context: &Context) -> Vec<String> {
+    let script = context.root.join(args.script());
+    if script.is_file() {
+        Vec::new()
+    } else {
+        vec![format!("Script {} does not exist.", script.display())]
+    }
+}
 
-/// Runs a script.
-pub fn run<W>(args: &serde_json::Value, logger: &mut Logger<W>) -> Option<()>
+/// Returns a boolean indicating whether the guards of a script task are met.
+/// Scripts without guards always have to be run, as there is no telling what
+/// they do.
+fn check(args: &Args, context: &Context) -> bool {
+    match args {
+        Args::Script(_) => false,
+        Args::Guarded(g) => guard::is_met(g.creates.as_deref(), g.unless.as_deref(), context),
+    }
+}
+
+/// Runs a script, capturing its output (stdout followed by stderr).
+///
+/// Relative script paths are resolved against the root of the dotfiles
+/// repository.
+fn run<W>(args: &Args, context: &Context, logger: &mut Logger<W>) -> Outcome
 where
     W: Write,
 {
-    if let Some(s) = &args.as_str() {
-        logger.info(&format!("Running script: {}", s));
-        if shell::run_script(&PathBuf::from(s)) {
-            logger.success("Done");
-            Some(())
-        } else {
-            logger.error("An error occured while running the script.");
-            None
-        }
+    let s = args.script();
+    logger.info(&format!("Running script: {}", s.display()));
+
+    let script = context.root.join(s);
+    let output = match shell::output(&script.to_string_lossy(), None) {
+        Ok(output) => output,
+        Err(e) => return Status::Failed(format!("Failed to run the script: {}", e)).into(),
+    };
+
+    let status = if output.status.success() {
+        Status::Changed
     } else {
-        logger.warn("Nothing to do.");
-        Some(())
+        Status::Failed(format!("The script failed ({}).", output.status))
+    };
+    let mut captured = String::from_utf8_lossy(&output.stdout).into_owned();
+    captured.push_str(&String::from_utf8_lossy(&output.stderr));
+
+    Outcome {
+        output: Some(captured),
+        ..Outcome::from(status)
+    }
+}
+
+/// Runs scripts from the dotfiles repository.
+pub struct Script;
+
+impl Module for Script {
+    type Args = Args;
+
+    fn name(&self) -> &'static str {
+        "script"
+    }
+
+    fn validate(&self, args: &Self::Args, context: &Context) -> Vec<String> {
+        validate(args, context)
+    }
+
+    fn check(&self, args: &Self::Args, context: &Context) -> bool {
+        check(args, context)
+    }
+
+    fn apply<W>(&self, args: &Self::Args, context: &Context, logger: &mut Logger<W>) -> Outcome
+    where
+        W: Write,
+    {
+        run(args, context, logger)
     }
 }
@@ -0,0 +1,106 @@
+//! Module for rendering templated files.
+
+use super::{Context, Module, Outcome, Status};
+use crate::dotfiles::link::{Linker, Mode, Strategy};
+use crate::dotfiles::manifest::Manifest;
+use crate::logger::Logger;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use shellexpand::tilde;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// The arguments of a template task.
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[schemars(rename = "TemplateArgs")]
+#[serde(deny_unknown_fields)]
+pub struct Args {
+    /// The template, relative to the repository root.
+    pub src: PathBuf,
+
+    /// The file to write the rendered output to.
+    pub dest: String,
+
+    /// What to do with an existing file at `dest`. Defaults to the
+    /// config-wide strategy.
+    pub conflict: Option<Strategy>,
+}
+
+/// Returns the problems with a template task that would stop it from running.
+fn validate(args: &Args, context: &Context) -> Vec<String> {
+    let src = context.root.join(&args.src);
+    if src.is_file() {
+        Vec::new()
+    } else {
+        vec![format!("Template {} does not exist.", src.display())]
+    }
+}
+
+/// Renders a template from the repository and writes the output to a file.
+///
+/// See `Args` for the arguments.
+///
+/// The output is tracked in the deployment manifest like any other dotfile.
+fn run<W>(args: &Args, context: &Context, logger: &mut Logger<W>) -> Outcome
+where
+    W: Write,
+{
+    let src = context.root.join(&args.src);
+    let dest = PathBuf::from(tilde(&args.dest).as_ref());
+
+    let manifest = match Manifest::load() {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            return Status::Failed(format!("Failed to load the deployment manifest: {}", e)).into()
+        }
+    };
+
+    let target = dest.parent().unwrap_or(&dest).to_path_buf();
+    let mut linker = Linker::new(&context.root, &target, manifest);
+    linker.mode = Mode::Template;
+    linker.conflict = context.conflict;
+    linker.facts = context.facts.clone();
+    linker.variables = context.variables.clone();
+    linker.diff = context.diff;
+
+    if let Some(conflict) = args.conflict {
+        linker.conflict = conflict;
+    }
+
+    logger.info(&format!("Rendering template: {}", src.display()));
+    let result = linker.deploy(&src, &dest, logger);
+
+    if let Err(e) = linker.manifest.save() {
+        return Status::Failed(format!("Failed to save the deployment manifest: {}", e)).into();
+    }
+
+    match result {
+        // Nothing was deployed because of existing files in the way.
+        Ok(_) if linker.changes == 0 && linker.skipped > 0 => Status::Skipped.into(),
+        Ok(_) if linker.changes == 0 => Status::Ok.into(),
+        Ok(_) => Status::Changed.into(),
+        Err(e) => Status::Failed(e.to_string()).into(),
+    }
+}
+
+/// Renders templates from the dotfiles repository into files.
+pub struct Template;
+
+impl Module for Template {
+    type Args = Args;
+
+    fn name(&self) -> &'static str {
+        "template"
+    }
+
+    fn validate(&self, args: &Self::Args, context: &Context) -> Vec<String> {
+        validate(args, context)
+    }
+
+    fn apply<W>(&self, args: &Self::Args, context: &Context, logger: &mut Logger<W>) -> Outcome
+    where
+        W: Write,
+    {
+        run(args, context, logger)
+    }
+}
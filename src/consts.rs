@@ -1,3 +1,38 @@
+// Config file locations (relative to the dotfiles repository root), in the
+// order they are looked up.
+pub const CONFIG_FILES: [&str; 8] = [
+    "dotman.json",
+    "dotman.yaml",
+    "dotman.yml",
+    "dotman.toml",
+    ".dotman/config.json",
+    ".dotman/config.yaml",
+    ".dotman/config.yml",
+    ".dotman/config.toml",
+];
+// The name of the files listing repository entries that are not deployed.
+pub const IGNORE_FILE: &str = ".dotmanignore";
+// Repository entries that are never deployed unless explicitly re-included
+// (gitignore syntax).
+pub const DEFAULT_IGNORE: [&str; 8] = [
+    ".git",
+    ".gitmodules",
+    ".dotman",
+    ".dotmanignore",
+    "dotman.json",
+    "dotman.yaml",
+    "dotman.yml",
+    "dotman.toml",
+];
+// The extension of dotfiles that are rendered as templates.
+pub const TEMPLATE_EXTENSION: &str = "tmpl";
+// The extension of dotfiles that are stored encrypted.
+pub const ENCRYPTED_EXTENSION: &str = "age";
+// The location of the key that secret dotfiles are encrypted with.
+pub const KEY_FILE: &str = "~/.config/dotman/key.txt";
+// The key of config objects that hold an encrypted value, e.g.
+// `{"$encrypted": "-----BEGIN AGE ENCRYPTED FILE-----..."}`.
+pub const ENCRYPTED_VALUE_KEY: &str = "$encrypted";
 pub const DEFAULT_TERM_WIDTH: u16 = 50; // 50 columns
 pub const DOTMAN_VERSION: &'static str = env!("CARGO_PKG_VERSION");
 pub const DOTMAN_LOGO: &str = "
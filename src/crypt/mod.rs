@@ -0,0 +1,274 @@
+//! Module for encrypting secret dotfiles.
+//!
+//! Secrets are encrypted with [age](https://age-encryption.org) using a key
+//! stored outside the repository (see `consts::KEY_FILE`). Encrypted files are
+//! committed with the `.age` extension and decrypted when they are deployed.
+//! Secrets in the config are stored as encrypted values (see
+//! `decrypt_values`).
+extern crate age;
+
+use crate::consts::{ENCRYPTED_EXTENSION, ENCRYPTED_VALUE_KEY, KEY_FILE};
+use age::secrecy::ExposeSecret;
+use age::x25519::{Identity, Recipient};
+use chrono::Local;
+use serde_json::Value;
+use shellexpand::tilde;
+use std::{
+    ffi::OsStr,
+    fs,
+    io::{self, ErrorKind, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+/// Returns the location of the key that secrets are encrypted with.
+pub fn key_file() -> PathBuf {
+    PathBuf::from(tilde(KEY_FILE).as_ref())
+}
+
+/// Loads a key from a file in the format written by `age-keygen`.
+///
+/// # Arguments
+///
+/// * `path` - The key file.
+pub fn load_key(path: &Path) -> io::Result<Identity> {
+    let contents = fs::read_to_string(path)?;
+
+    contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .ok_or_else(|| io::Error::other(format!("No key found in {}", path.display())))?
+        .parse()
+        .map_err(|e| io::Error::other(format!("Invalid key in {}: {}", path.display(), e)))
+}
+
+/// Generates a new key and writes it to a file that only the current user can
+/// read. Returns the key.
+///
+/// # Arguments
+///
+/// * `path` - The key file. It must not exist yet.
+pub fn generate_key(path: &Path) -> io::Result<Identity> {
+    let key = Identity::generate();
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+
+    writeln!(file, "# created: {}", Local::now().to_rfc3339())?;
+    writeln!(file, "# public key: {}", key.to_public())?;
+    writeln!(file, "{}", key.to_string().expose_secret())?;
+
+    Ok(key)
+}
+
+/// Encrypts some contents so that they can be decrypted with the key. The
+/// result is ASCII armored, so that it can be diffed and merged like text.
+///
+/// # Arguments
+///
+/// * `recipient` - The public half of the key.
+/// * `plaintext` - The contents to encrypt.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dotman::crypt;
+///
+/// let key = crypt::load_key(&crypt::key_file()).unwrap();
+/// let encrypted = crypt::encrypt(&key.to_public(), b"machine example.com").unwrap();
+///
+/// assert_eq!(crypt::decrypt(&key, encrypted.as_bytes()).unwrap(), b"machine example.com");
+/// ```
+pub fn encrypt(recipient: &Recipient, plaintext: &[u8]) -> io::Result<String> {
+    age::encrypt_and_armor(recipient, plaintext).map_err(io::Error::other)
+}
+
+/// Decrypts contents encrypted with `encrypt`.
+///
+/// # Arguments
+///
+/// * `key` - The key that the contents were encrypted for.
+/// * `ciphertext` - The encrypted contents.
+pub fn decrypt(key: &Identity, ciphertext: &[u8]) -> io::Result<Vec<u8>> {
+    age::decrypt(key, ciphertext).map_err(io::Error::other)
+}
+
+/// Loads the key from the key file, with a helpful error if there isn't one.
+fn load_default_key() -> io::Result<Identity> {
+    let key_file = key_file();
+
+    match load_key(&key_file) {
+        Err(e) if e.kind() == ErrorKind::NotFound => Err(io::Error::other(format!(
+            "no key found at {}",
+            key_file.display()
+        ))),
+        result => result,
+    }
+}
+
+/// Decrypts a file with the key in the key file.
+///
+/// # Arguments
+///
+/// * `path` - The encrypted file.
+pub fn decrypt_file(path: &Path) -> io::Result<Vec<u8>> {
+    let key = load_default_key()
+        .map_err(|e| io::Error::other(format!("Failed to decrypt {}: {}", path.display(), e)))?;
+
+    decrypt(&key, &fs::read(path)?)
+        .map_err(|e| io::Error::other(format!("Failed to decrypt {}: {}", path.display(), e)))
+}
+
+/// Encrypts a file, writing the result next to it with the `.age` extension
+/// added. Returns the path of the encrypted file.
+///
+/// # Arguments
+///
+/// * `recipient` - The public half of the key.
+/// * `path` - The file to encrypt.
+pub fn encrypt_file(recipient: &Recipient, path: &Path) -> io::Result<PathBuf> {
+    if path.extension().is_some_and(|e| e == ENCRYPTED_EXTENSION) {
+        return Err(io::Error::other("it is already encrypted"));
+    }
+
+    let mut encrypted = path.as_os_str().to_owned();
+    encrypted.push(".");
+    encrypted.push(ENCRYPTED_EXTENSION);
+    let encrypted = PathBuf::from(encrypted);
+
+    fs::write(&encrypted, encrypt(recipient, &fs::read(path)?)?)?;
+
+    Ok(encrypted)
+}
+
+/// Decrypts a file with the key in the key file, writing the result next to it
+/// without the `.age` extension. Returns the path of the decrypted file.
+///
+/// # Arguments
+///
+/// * `path` - The encrypted file.
+pub fn decrypt_to_file(path: &Path) -> io::Result<PathBuf> {
+    if path.extension() != Some(OsStr::new(ENCRYPTED_EXTENSION)) {
+        return Err(io::Error::other(format!(
+            "encrypted files have to end in .{}",
+            ENCRYPTED_EXTENSION
+        )));
+    }
+
+    let plaintext = decrypt_file(path)?;
+    let decrypted = path.with_extension("");
+
+    fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&decrypted)?
+        .write_all(&plaintext)?;
+
+    Ok(decrypted)
+}
+
+/// Replaces every encrypted value in a config (an object with the single key
+/// `$encrypted` and the encrypted string as its value) with the decrypted
+/// string, collecting the decrypted strings in `secrets`.
+///
+/// The key is only loaded if there is something to decrypt.
+///
+/// # Arguments
+///
+/// * `value` - The config, or a part of it.
+/// * `secrets` - The decrypted strings found so far.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dotman::crypt;
+///
+/// let mut args = serde_json::json!({ "token": { "$encrypted": "-----BEGIN AGE ENCRYPTED FILE-----..." } });
+/// let mut secrets = Vec::new();
+///
+/// crypt::decrypt_values(&mut args, &mut secrets).unwrap();
+/// assert_eq!(args["token"], secrets[0]);
+/// ```
+pub fn decrypt_values(value: &mut Value, secrets: &mut Vec<String>) -> io::Result<()> {
+    decrypt_values_with(value, &mut None, secrets)
+}
+
+/// Replaces every encrypted value in a config (see `decrypt_values`) with
+/// `****`, without decrypting anything. The result has the shape of the
+/// decrypted config, but nothing secret in it.
+///
+/// # Arguments
+///
+/// * `value` - The config, or a part of it.
+///
+/// # Examples
+///
+/// ```
+/// use dotman::crypt;
+///
+/// let mut args = serde_json::json!({ "token": { "$encrypted": "-----BEGIN AGE ENCRYPTED FILE-----..." } });
+///
+/// crypt::mask_values(&mut args);
+/// assert_eq!(args["token"], "****");
+/// ```
+pub fn mask_values(value: &mut Value) {
+    match value {
+        Value::Object(map) if map.len() == 1 && map.contains_key(ENCRYPTED_VALUE_KEY) => {
+            *value = Value::String(String::from("****"));
+        }
+        Value::Object(map) => map.values_mut().for_each(mask_values),
+        Value::Array(values) => values.iter_mut().for_each(mask_values),
+        _ => (),
+    }
+}
+
+/// Does the work of `decrypt_values`, loading the key into `key` on first use.
+fn decrypt_values_with(
+    value: &mut Value,
+    key: &mut Option<Identity>,
+    secrets: &mut Vec<String>,
+) -> io::Result<()> {
+    match value {
+        Value::Object(map) if map.len() == 1 && map.contains_key(ENCRYPTED_VALUE_KEY) => {
+            let ciphertext = map[ENCRYPTED_VALUE_KEY].as_str().ok_or_else(|| {
+                io::Error::other(format!("{} has to be a string", ENCRYPTED_VALUE_KEY))
+            })?;
+
+            if key.is_none() {
+                *key = Some(load_default_key()?);
+            }
+
+            let plaintext = decrypt(key.as_ref().unwrap(), ciphertext.as_bytes())?;
+            let plaintext = String::from_utf8(plaintext).map_err(io::Error::other)?;
+
+            secrets.push(plaintext.clone());
+            *value = Value::String(plaintext);
+        }
+
+        Value::Object(map) => {
+            for v in map.values_mut() {
+                decrypt_values_with(v, key, secrets)?;
+            }
+        }
+
+        Value::Array(values) => {
+            for v in values.iter_mut() {
+                decrypt_values_with(v, key, secrets)?;
+            }
+        }
+
+        _ => (),
+    }
+
+    Ok(())
+}
@@ -1,7 +1,11 @@
 //! Console display and pretty printing utilities.
 use crate::config::Stats;
 use crate::consts::*;
-use ansi_term::Color;
+use ansi_term::{ANSIString, Color, Style};
+use similar::{ChangeTag, TextDiff};
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
 use terminal_size::{terminal_size, Height, Width};
 
 // ? Implementation could probably be improved.
@@ -18,7 +22,8 @@ pub fn print_logo() {
 }
 
 // ? Implementation could probably be improved.
-/// Prints a banner on the console with the specified message.
+/// Returns a banner with the specified message, for the caller to write (e.g.
+/// with `Logger::print`).
 ///
 /// The function allows the caller to specify the decorator to use and the
 /// desired width of the banner. If neither of these are specified OR if the
@@ -29,7 +34,7 @@ pub fn print_logo() {
 /// * `msg` - The message to display.
 /// * `dec` - The character to use as a decorator.
 /// * `width` - The width of the banner.
-pub fn banner(msg: &str, dec: Option<char>, width: Option<u16>) {
+pub fn banner(msg: &str, dec: Option<char>, width: Option<u16>) -> String {
     let size = terminal_size();
     let dec = match dec {
         Some(d) => d,
@@ -56,26 +61,167 @@ pub fn banner(msg: &str, dec: Option<char>, width: Option<u16>) {
         iter += 1;
     }
 
-    // Print the banner.
-    println!("{}", line);
-    println!("{}", msg);
-    println!("{}", line);
-    println!();
+    format!("{}\n{}\n{}\n\n", line, msg, line)
 }
 
 // TODO: Display total time taken.
-/// Pretty-prints the stats of specified tasks.
+/// Returns the pretty-printed stats of specified tasks, for the caller to
+/// write (e.g. with `Logger::print`).
 ///
 /// # Arguments
 ///
 /// * `stats` - The task statistics to display.
-pub fn stats(stats: &Stats) {
-    println!(
-        "TOTAL: {}\tSUCCESS: {}\tFAILED: {}\n",
-        Color::Blue.bold().paint(&format!("{}", stats.total_tasks)),
-        Color::Green
+pub fn stats(stats: &Stats) -> String {
+    format!(
+        "TOTAL: {}\tOK: {}\tCHANGED: {}\tSKIPPED: {}\tFAILED: {}\n\n",
+        Color::Blue.bold().paint(format!("{}", stats.total_tasks)),
+        Color::Green.bold().paint(format!("{}", stats.ok_tasks)),
+        Color::Yellow
             .bold()
-            .paint(&format!("{}", stats.success_tasks)),
-        Color::Red.bold().paint(&format!("{}", stats.failed_tasks)),
+            .paint(format!("{}", stats.changed_tasks)),
+        Color::Cyan.bold().paint(format!("{}", stats.skipped_tasks)),
+        Color::Red.bold().paint(format!("{}", stats.failed_tasks)),
+    )
+}
+
+/// Prints a table with a bold header row, aligning the columns.
+///
+/// # Arguments
+///
+/// * `headers` - The column headers.
+/// * `rows` - The rows of the table, each with one (optionally colored) cell
+///   per column.
+///
+/// # Examples
+///
+/// ```
+/// use ansi_term::Color;
+/// use dotman::display;
+///
+/// display::table(
+///     &["STATE", "TARGET"],
+///     &[vec![Color::Red.paint("missing"), Color::White.paint("~/.vimrc")]],
+/// );
+/// ```
+pub fn table(headers: &[&str], rows: &[Vec<ANSIString>]) {
+    // NOTE: Dereferencing a colored string gives the text without the escape
+    // codes, which is what the columns have to be aligned by.
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header: Vec<String> = headers
+        .iter()
+        .zip(widths.iter())
+        .map(|(h, w)| format!("{:<1$}", h, w))
+        .collect();
+    println!(
+        "{}",
+        Style::new().bold().paint(header.join("  ").trim_end())
     );
+
+    for row in rows.iter() {
+        let mut line = String::new();
+
+        for (i, (cell, width)) in row.iter().zip(widths.iter()).enumerate() {
+            line.push_str(&cell.to_string());
+
+            if i + 1 < row.len() {
+                line.push_str(&" ".repeat(width - cell.chars().count() + 2));
+            }
+        }
+
+        println!("{}", line);
+    }
+
+    println!();
+}
+
+/// Returns a colored unified diff between two versions of a file.
+///
+/// # Arguments
+///
+/// * `old` - The current contents.
+/// * `new` - The new contents.
+/// * `old_name` - The name to label the current contents with.
+/// * `new_name` - The name to label the new contents with.
+///
+/// # Examples
+///
+/// ```
+/// use dotman::display;
+///
+/// let diff = display::diff("set number\n", "set relativenumber\n", "~/.vimrc", "vim/.vimrc");
+/// print!("{}", diff);
+/// ```
+pub fn diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let diff = TextDiff::from_lines(old, new);
+    let mut out = format!(
+        "{}\n{}\n",
+        Style::new().bold().paint(format!("--- {}", old_name)),
+        Style::new().bold().paint(format!("+++ {}", new_name)),
+    );
+
+    for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+        out.push_str(&format!(
+            "{}\n",
+            Color::Cyan.paint(hunk.header().to_string())
+        ));
+
+        for change in hunk.iter_changes() {
+            let (sign, style) = match change.tag() {
+                ChangeTag::Delete => ('-', Color::Red.normal()),
+                ChangeTag::Insert => ('+', Color::Green.normal()),
+                ChangeTag::Equal => (' ', Style::new()),
+            };
+            let line = format!("{}{}", sign, change.value().trim_end_matches('\n'));
+
+            out.push_str(&format!("{}\n", style.paint(line)));
+            if change.missing_newline() {
+                out.push_str("\\ No newline at end of file\n");
+            }
+        }
+    }
+
+    out
+}
+
+/// Prints text through the user's pager (`$PAGER`, or `less -R` if it isn't
+/// set) when stdout is a terminal, and directly otherwise.
+///
+/// # Arguments
+///
+/// * `text` - The text to display.
+pub fn page(text: &str) {
+    if !io::stdout().is_terminal() {
+        print!("{}", text);
+        return;
+    }
+
+    let pager = env::var("PAGER").unwrap_or_else(|_| String::from("less -R"));
+    let mut args = pager.split_whitespace();
+
+    let child = args.next().and_then(|program| {
+        Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .spawn()
+            .ok()
+    });
+
+    match child {
+        Some(mut child) => {
+            // NOTE: The pager may be quit before reading everything, so a
+            // failed write is not an error.
+            if let Some(mut stdin) = child.stdin.take() {
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            let _ = child.wait();
+        }
+        // ? Should a broken pager be reported to the user?
+        None => print!("{}", text),
+    }
 }
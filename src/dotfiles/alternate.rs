@@ -0,0 +1,221 @@
+//! Module for selecting alternate versions of dotfiles.
+//!
+//! A dotfile can have several alternates, each named after the dotfile
+//! followed by `##` and a comma-separated list of conditions, e.g.
+//! `gitconfig##host.laptop` or `bashrc##os.linux,class.work`. The supported
+//! conditions are:
+//!
+//! * `default` - Always matches.
+//! * `host.<hostname>` (or `h.<hostname>`)
+//! * `user.<user>` (or `u.<user>`)
+//! * `os.<os>` (or `o.<os>`)
+//! * `distro.<distro>` (or `d.<distro>`)
+//! * `arch.<arch>` (or `a.<arch>`)
+//! * `class.<class>` (or `c.<class>`)
+//!
+//! Of the alternates whose conditions all match, the one with the most
+//! conditions is deployed under the dotfile's name. A plain file with the same
+//! name is treated like an alternate with no conditions.
+use crate::facts::Facts;
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+
+/// The separator between a dotfile's name and its conditions.
+const SEPARATOR: &str = "##";
+
+/// Splits a file name into the name of the dotfile and the conditions of the
+/// alternate (if it is one).
+///
+/// # Arguments
+///
+/// * `name` - The file name.
+///
+/// # Examples
+///
+/// ```
+/// use dotman::dotfiles::alternate;
+///
+/// assert_eq!(alternate::split("vimrc##os.linux"), ("vimrc", Some("os.linux")));
+/// assert_eq!(alternate::split("vimrc"), ("vimrc", None));
+/// ```
+pub fn split(name: &str) -> (&str, Option<&str>) {
+    match name.find(SEPARATOR) {
+        Some(i) => (&name[..i], Some(&name[i + SEPARATOR.len()..])),
+        None => (name, None),
+    }
+}
+
+/// Returns the number of conditions of an alternate if all of them match the
+/// facts, and `None` otherwise.
+///
+/// # Arguments
+///
+/// * `conditions` - The comma-separated list of conditions.
+/// * `facts` - The facts about the current system.
+pub fn score(conditions: &str, facts: &Facts) -> Option<usize> {
+    let mut score = 0;
+
+    for condition in conditions.split(',') {
+        if condition == "default" {
+            continue;
+        }
+
+        let (key, value) = match condition.find('.') {
+            Some(i) => (&condition[..i], &condition[i + 1..]),
+            None => return None,
+        };
+
+        let fact = match key {
+            "host" | "h" => Some(&facts.hostname),
+            "user" | "u" => Some(&facts.user),
+            "os" | "o" => Some(&facts.os),
+            "distro" | "d" => facts.distro.as_ref(),
+            "arch" | "a" => Some(&facts.arch),
+            "class" | "c" => facts.class.as_ref(),
+            _ => None,
+        };
+
+        match fact {
+            Some(fact) if fact.eq_ignore_ascii_case(value) => score += 1,
+            _ => return None,
+        }
+    }
+
+    Some(score)
+}
+
+/// Selects which entries of a directory to deploy, returning pairs of the
+/// entry's name and the name to deploy it under.
+///
+/// Entries that aren't alternates are deployed under their own name, unless a
+/// matching alternate of the same dotfile exists.
+///
+/// # Arguments
+///
+/// * `names` - The names of the entries in the directory.
+/// * `facts` - The facts about the current system.
+pub fn select(names: Vec<OsString>, facts: &Facts) -> Vec<(OsString, OsString)> {
+    // The best candidate for each dotfile, along with its score.
+    let mut selected: HashMap<OsString, (OsString, usize)> = HashMap::new();
+    let mut order = Vec::new();
+
+    for name in names.into_iter() {
+        let (base, score) = match name.to_str().map(split) {
+            Some((base, Some(conditions))) => match score(conditions, facts) {
+                Some(score) => (OsString::from(base), score),
+                None => continue,
+            },
+            _ => (name.clone(), 0),
+        };
+
+        match selected.get(&base) {
+            // NOTE: On a tie, the first candidate found is kept.
+            Some((_, best)) if *best >= score => continue,
+            Some(_) => (),
+            None => order.push(base.clone()),
+        }
+
+        selected.insert(base, (name, score));
+    }
+
+    order
+        .into_iter()
+        .map(|base| {
+            let name = selected.remove(&base).unwrap().0;
+            (name, base)
+        })
+        .collect()
+}
+
+/// Returns the name of the dotfile that a (possibly alternate) file name
+/// belongs to.
+///
+/// # Arguments
+///
+/// * `name` - The file name.
+pub fn base(name: &OsStr) -> &OsStr {
+    match name.to_str().map(split) {
+        Some((base, Some(_))) => OsStr::new(base),
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facts() -> Facts {
+        Facts {
+            hostname: String::from("laptop"),
+            os: String::from("linux"),
+            class: Some(String::from("work")),
+            ..Facts::default()
+        }
+    }
+
+    fn names(names: &[&str]) -> Vec<OsString> {
+        names.iter().map(OsString::from).collect()
+    }
+
+    fn selected(names: Vec<OsString>) -> Vec<(String, String)> {
+        select(names, &facts())
+            .into_iter()
+            .map(|(name, base)| (name.into_string().unwrap(), base.into_string().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn most_specific_alternate_is_selected() {
+        let names = names(&[
+            "bashrc",
+            "bashrc##default",
+            "bashrc##os.linux",
+            "bashrc##os.linux,class.work",
+            "bashrc##os.linux,class.home",
+            "bashrc##host.desktop",
+        ]);
+
+        assert_eq!(
+            selected(names),
+            vec![(
+                String::from("bashrc##os.linux,class.work"),
+                String::from("bashrc")
+            )]
+        );
+    }
+
+    #[test]
+    fn plain_files_are_kept_without_matching_alternates() {
+        let names = names(&["vimrc", "vimrc##host.desktop", "zshrc##h.laptop"]);
+
+        assert_eq!(
+            selected(names),
+            vec![
+                (String::from("vimrc"), String::from("vimrc")),
+                (String::from("zshrc##h.laptop"), String::from("zshrc")),
+            ]
+        );
+    }
+
+    #[test]
+    fn first_alternate_wins_a_tie() {
+        let names = names(&["gitconfig##class.work", "gitconfig##os.linux"]);
+
+        assert_eq!(
+            selected(names),
+            vec![(
+                String::from("gitconfig##class.work"),
+                String::from("gitconfig")
+            )]
+        );
+    }
+
+    #[test]
+    fn unknown_conditions_never_match() {
+        assert_eq!(score("shell.zsh", &facts()), None);
+        assert_eq!(score("linux", &facts()), None);
+        assert_eq!(score("distro.arch", &facts()), None);
+        assert_eq!(score("OS.Linux", &facts()), None);
+        assert_eq!(score("os.Linux,default", &facts()), Some(1));
+    }
+}
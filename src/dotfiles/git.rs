@@ -1,15 +1,22 @@
 //! Module for working with Git.
 // TODO: Add authentication support for cloning private repos.
+use super::ignore;
 use fs_extra::dir::{move_dir, CopyOptions};
-use git2::{Error as GitError, Repository};
-use std::{fs, path::PathBuf};
+use git2::{
+    build::CheckoutBuilder, BranchType, Error as GitError, ErrorCode, IndexAddOption, Repository,
+    StatusOptions,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 /// Opens an existing repository on the filesystem.
 ///
 /// # Arguments
 ///
 /// * `src` - The path to the repository on the filesystem.
-fn open(src: &PathBuf) -> Result<Repository, GitError> {
+pub fn open(src: &PathBuf) -> Result<Repository, GitError> {
     Repository::open(src)
 }
 
@@ -24,8 +31,8 @@ fn open(src: &PathBuf) -> Result<Repository, GitError> {
 /// # Examples
 ///
 /// ## Forcefully cloning when destination exists
-/// ```
-/// use dotfiles;
+/// ```no_run
+/// use dotman::dotfiles;
 ///
 /// let src = "https://github.com/john-doe/hello-world";
 /// let dest = std::path::PathBuf::from("/home/johndoe/hello-world");
@@ -33,8 +40,8 @@ fn open(src: &PathBuf) -> Result<Repository, GitError> {
 /// ```
 ///
 /// ## Skip cloning if repository already exists locally
-/// ```
-/// use dotfiles;
+/// ```no_run
+/// use dotman::dotfiles;
 ///
 /// let src = "https://github.com/john-doe/hello-world";
 /// let dest = std::path::PathBuf::from("/home/johndoe/hello-world");
@@ -78,3 +85,200 @@ pub fn clone(src: &str, dest: &PathBuf, force: bool) -> Result<Repository, GitEr
     // the repository with no further checks required.
     Repository::clone(src, &dest)
 }
+
+/// Fetches the current branch from a remote and fast-forwards the local branch
+/// to match it.
+///
+/// Returns `true` if the local branch was updated and `false` if it was
+/// already up to date. Diverged histories are never merged, and uncommitted
+/// changes that the update would overwrite are never discarded; an error is
+/// returned instead (before anything is changed) so that local changes are not
+/// lost. Deployed dotfiles link into the working directory, so such changes are
+/// often edits made to the dotfiles in place.
+///
+/// # Arguments
+///
+/// * `repo` - The local repository.
+/// * `remote` - The name of the remote to pull from (e.g. `origin`).
+///
+/// # Examples
+///
+/// ```no_run
+/// use dotman::dotfiles;
+///
+/// let dest = std::path::PathBuf::from("/home/johndoe/hello-world");
+/// let repo = dotfiles::git::open(&dest).unwrap();
+/// dotfiles::git::pull(&repo, "origin").unwrap();
+/// ```
+pub fn pull(repo: &Repository, remote: &str) -> Result<bool, GitError> {
+    let head = repo.head()?;
+    let branch = match head.shorthand() {
+        Some(branch) => branch.to_string(),
+        None => return Err(GitError::from_str("HEAD does not point to a branch.")),
+    };
+
+    let mut remote = repo.find_remote(remote)?;
+    remote.fetch(&[&branch], None, None)?;
+
+    let fetch_head = repo.find_reference("FETCH_HEAD")?;
+    let fetch_commit = repo.reference_to_annotated_commit(&fetch_head)?;
+    let (analysis, _) = repo.merge_analysis(&[&fetch_commit])?;
+
+    if analysis.is_up_to_date() {
+        return Ok(false);
+    }
+
+    if !analysis.is_fast_forward() {
+        return Err(GitError::from_str(
+            "Local and remote branches have diverged and cannot be fast-forwarded.",
+        ));
+    }
+
+    // Update the working directory first, so that the branch is left alone if
+    // that fails. A safe checkout only touches files without local changes and
+    // fails up front if any local change is in the way.
+    let target = repo.find_object(fetch_commit.id(), None)?;
+    if let Err(e) = repo.checkout_tree(&target, Some(CheckoutBuilder::new().safe())) {
+        return Err(match e.code() {
+            ErrorCode::Conflict => GitError::from_str(
+                "Uncommitted changes would be overwritten by the pull. Commit or stash them first.",
+            ),
+            _ => e,
+        });
+    }
+
+    // Move the branch reference to the fetched commit.
+    let refname = format!("refs/heads/{}", branch);
+    let mut reference = repo.find_reference(&refname)?;
+    reference.set_target(fetch_commit.id(), "dotman: fast-forward")?;
+    repo.set_head(&refname)?;
+
+    Ok(true)
+}
+
+/// Stages files or directories in the repository's index.
+///
+/// # Arguments
+///
+/// * `repo` - The local repository.
+/// * `paths` - The paths to stage, either absolute or relative to the
+///   repository root.
+pub fn stage(repo: &Repository, paths: &[PathBuf]) -> Result<(), GitError> {
+    let root = repo.workdir().unwrap_or_else(|| Path::new(""));
+    let mut index = repo.index()?;
+
+    for path in paths.iter() {
+        let rel = path.strip_prefix(root).unwrap_or(path);
+        index.add_all([rel].iter(), IndexAddOption::DEFAULT, None)?;
+    }
+
+    index.write()
+}
+
+/// Makes sure that a file in the working directory is never committed by
+/// adding it to the `.gitignore` at the root of the repository, unless it is
+/// ignored already. Fails if the file is tracked, as ignoring it would have no
+/// effect then.
+///
+/// # Arguments
+///
+/// * `repo` - The local repository.
+/// * `path` - The file, either absolute or relative to the repository root.
+pub fn exclude(repo: &Repository, path: &Path) -> Result<(), GitError> {
+    let root = repo.workdir().unwrap_or_else(|| Path::new(""));
+    let rel = path.strip_prefix(root).unwrap_or(path);
+
+    if repo.index()?.get_path(rel, 0).is_some() {
+        return Err(GitError::from_str(&format!(
+            "{} is tracked by the repository",
+            rel.display()
+        )));
+    }
+    if repo.is_path_ignored(rel)? {
+        return Ok(());
+    }
+
+    ignore::add_pattern(&root.join(".gitignore"), &format!("/{}", rel.display()))
+        .map_err(|e| GitError::from_str(&e.to_string()))
+}
+
+/// Fetches all branches from a remote, updating the remote-tracking branches.
+///
+/// # Arguments
+///
+/// * `repo` - The local repository.
+/// * `remote` - The name of the remote to fetch from (e.g. `origin`).
+pub fn fetch(repo: &Repository, remote: &str) -> Result<(), GitError> {
+    repo.find_remote(remote)?.fetch(&[] as &[&str], None, None)
+}
+
+/// Returns the number of commits that the current branch is ahead of and
+/// behind its upstream branch, or `None` if it doesn't have an upstream branch.
+///
+/// The upstream branch is only as recent as the last fetch.
+///
+/// # Arguments
+///
+/// * `repo` - The local repository.
+pub fn ahead_behind(repo: &Repository) -> Result<Option<(usize, usize)>, GitError> {
+    // There is no branch to compare before the first commit, or when a commit
+    // is checked out directly.
+    let head = match repo.head() {
+        Ok(head) if head.is_branch() => head,
+        Ok(_) => return Ok(None),
+        Err(e) if e.code() == ErrorCode::UnbornBranch => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let name = match head.shorthand() {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+
+    let upstream = match repo.find_branch(name, BranchType::Local)?.upstream() {
+        Ok(upstream) => upstream,
+        Err(e) if e.code() == ErrorCode::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    match (head.target(), upstream.get().target()) {
+        (Some(local), Some(remote)) => repo.graph_ahead_behind(local, remote).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Returns the files in the working directory that have uncommitted changes,
+/// along with a short description of each change (e.g. `modified`).
+///
+/// # Arguments
+///
+/// * `repo` - The local repository.
+pub fn changes(repo: &Repository) -> Result<Vec<(String, &'static str)>, GitError> {
+    let mut options = StatusOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+
+    let mut changes = Vec::new();
+    for entry in repo.statuses(Some(&mut options))?.iter() {
+        let status = entry.status();
+        let change = if status.is_conflicted() {
+            "conflicted"
+        } else if status.is_wt_new() {
+            "untracked"
+        } else if status.is_index_new() {
+            "added"
+        } else if status.is_wt_deleted() || status.is_index_deleted() {
+            "deleted"
+        } else if status.is_wt_renamed() || status.is_index_renamed() {
+            "renamed"
+        } else if status.is_ignored() {
+            continue;
+        } else {
+            "modified"
+        };
+
+        if let Some(path) = entry.path() {
+            changes.push((path.to_string(), change));
+        }
+    }
+
+    Ok(changes)
+}
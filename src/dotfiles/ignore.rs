@@ -0,0 +1,150 @@
+//! Module for excluding files in the repository from deployment.
+//!
+//! Files are excluded using `.dotmanignore` files, which use the same syntax as
+//! `.gitignore` files. The one at the root of the repository applies to every
+//! package (with patterns relative to the root), and each package can have one
+//! of its own (with patterns relative to the package). Patterns in a package's
+//! ignore file take precedence, so it can re-include (`!pattern`) files that
+//! the repository ignores.
+//!
+//! Git's and dotman's own files (see `consts::DEFAULT_IGNORE`) are always
+//! ignored, unless an ignore file explicitly re-includes them.
+//!
+//! A folded directory is linked as a whole, so directories that contain ignored
+//! files are never folded (see `link::Linker`).
+use crate::consts::{DEFAULT_IGNORE, IGNORE_FILE};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+/// Builds a matcher from the ignore file in a directory (if there is one) and
+/// the specified patterns, which come first.
+fn build(dir: &Path, defaults: &[&str]) -> io::Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new(dir);
+
+    for pattern in defaults.iter() {
+        builder.add_line(None, pattern).map_err(io::Error::other)?;
+    }
+
+    let file = dir.join(IGNORE_FILE);
+    if file.is_file() {
+        if let Some(e) = builder.add(&file) {
+            return Err(io::Error::other(e));
+        }
+    }
+
+    builder.build().map_err(io::Error::other)
+}
+
+/// Appends a pattern to an ignore file (a `.dotmanignore` or `.gitignore`),
+/// creating the file if it doesn't exist.
+///
+/// # Arguments
+///
+/// * `file` - The ignore file.
+/// * `pattern` - The pattern to add.
+pub fn add_pattern(file: &Path, pattern: &str) -> io::Result<()> {
+    let contents = fs::read_to_string(file).unwrap_or_default();
+    let mut file = fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(file)?;
+
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        writeln!(file)?;
+    }
+    writeln!(file, "{}", pattern)
+}
+
+/// Excludes a file in the repository from deployment by adding it to the
+/// repository's ignore file, unless it is ignored already.
+///
+/// # Arguments
+///
+/// * `root` - The root directory of the dotfiles repository.
+/// * `path` - The file, either absolute or relative to `root`.
+pub fn exclude(root: &Path, path: &Path) -> io::Result<()> {
+    let rel = path.strip_prefix(root).unwrap_or(path);
+
+    if build(root, &DEFAULT_IGNORE)?
+        .matched(rel, false)
+        .is_ignore()
+    {
+        return Ok(());
+    }
+
+    add_pattern(&root.join(IGNORE_FILE), &format!("/{}", rel.display()))
+}
+
+/// Represents the ignore rules that apply to a package.
+///
+/// # Fields
+///
+/// * `root` - The rules of the repository (including the built-in ones).
+/// * `package` - The rules of the package.
+#[derive(Clone, Debug)]
+pub struct Ignore {
+    root: Gitignore,
+    package: Gitignore,
+}
+
+impl Ignore {
+    /// Loads the ignore rules that apply to a package.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The root directory of the dotfiles repository.
+    /// * `package` - The package directory. Use the repository root when
+    ///   linking the whole repository.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dotman::dotfiles::ignore::Ignore;
+    /// use std::path::Path;
+    ///
+    /// let root = Path::new("/home/johndoe/dotfiles");
+    /// let ignore = Ignore::load(root, &root.join("vim")).unwrap();
+    ///
+    /// assert!(ignore.is_ignored(&root.join("vim/.git"), true));
+    /// ```
+    pub fn load(root: &Path, package: &Path) -> io::Result<Self> {
+        Ok(Self {
+            root: build(root, &DEFAULT_IGNORE)?,
+            package: if package == root {
+                Gitignore::empty()
+            } else {
+                build(package, &[])?
+            },
+        })
+    }
+
+    /// Returns a boolean indicating whether a path in the repository must not
+    /// be deployed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path in the repository.
+    /// * `is_dir` - Whether the path is a directory.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let matched = self.package.matched(path, is_dir);
+
+        if matched.is_none() {
+            self.root.matched(path, is_dir).is_ignore()
+        } else {
+            matched.is_ignore()
+        }
+    }
+}
+
+impl Default for Ignore {
+    fn default() -> Self {
+        Self {
+            root: Gitignore::empty(),
+            package: Gitignore::empty(),
+        }
+    }
+}
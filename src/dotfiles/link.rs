@@ -0,0 +1,1363 @@
+//! Module for deploying dotfiles by symlinking them into a target directory.
+//!
+//! Linking works much like GNU stow: every entry in a package directory is
+//! linked to the same relative location in the target directory. Directories
+//! that don't exist in the target are linked as a whole ("folded"), and a
+//! folded directory is split back into individual links ("unfolded") as soon
+//! as another package needs to place files inside it.
+//!
+//! Existing files that get in the way are handled according to a conflict
+//! `Strategy`, and alternate versions of dotfiles (see the `alternate` module)
+//! are deployed under the dotfile's name. Files excluded by `.dotmanignore`
+//! files (see the `ignore` module) are never deployed.
+use super::alternate;
+use super::ignore::Ignore;
+use super::manifest::{hash, hash_contents, Entry, Kind, Manifest};
+use super::state_dir;
+use super::status;
+use crate::consts::{ENCRYPTED_EXTENSION, TEMPLATE_EXTENSION};
+use crate::crypt;
+use crate::facts::Facts;
+use crate::logger::Logger;
+use crate::template;
+use chrono::Local;
+use fs_extra::dir::CopyOptions;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, ErrorKind, Write},
+    os::unix::fs::{symlink, OpenOptionsExt},
+    path::{Path, PathBuf},
+};
+
+/// Represents what to do when a file already exists where a dotfile has to be
+/// deployed.
+///
+/// # Variants
+///
+/// * `Backup` - Move the existing file to a timestamped backup directory.
+/// * `Overwrite` - Remove the existing file.
+/// * `Skip` - Leave the existing file alone and don't deploy the dotfile.
+/// * `Adopt` - Move the existing file into the repository, replacing the dotfile.
+/// * `Prompt` - Ask the user which of the above to do (see `Logger::ask`). The
+///   file is skipped if there is no one to answer.
+#[derive(Clone, Copy, Debug, Default, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy {
+    #[default]
+    Backup,
+    Overwrite,
+    Skip,
+    Adopt,
+    Prompt,
+}
+
+/// Represents how a dotfile is deployed into the target directory.
+///
+/// # Variants
+///
+/// * `Symlink` - Link to the file (or whole directory) in the repository.
+/// * `Copy` - Copy the file. Useful for programs that replace their files
+///   atomically or can't follow links (e.g. Flatpak sandboxes).
+/// * `Hardlink` - Hard link the file. The repository and target have to be on
+///   the same filesystem.
+/// * `Template` - Render the file as a template (see the `template` module)
+///   and write the output. Files ending in `.tmpl` are always rendered and
+///   deployed without the suffix.
+/// * `Encrypted` - Decrypt the file (see the `crypt` module) and write the
+///   plaintext, readable only by the current user. Files ending in `.age` are
+///   always decrypted and deployed without the suffix.
+///
+/// Directories can only be symlinked; in the other modes they are created in
+/// the target and their contents deployed individually.
+#[derive(Clone, Copy, Debug, Default, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    #[default]
+    Symlink,
+    Copy,
+    Hardlink,
+    Template,
+    Encrypted,
+}
+
+impl Mode {
+    /// Returns the extension that source files deployed in this mode have, but
+    /// their deployed copies don't.
+    fn extension(self) -> Option<&'static str> {
+        match self {
+            Mode::Template => Some(TEMPLATE_EXTENSION),
+            Mode::Encrypted => Some(ENCRYPTED_EXTENSION),
+            _ => None,
+        }
+    }
+}
+
+impl From<Mode> for Kind {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Symlink => Kind::Symlink,
+            Mode::Copy => Kind::Copy,
+            Mode::Hardlink => Kind::Hardlink,
+            Mode::Template => Kind::Template,
+            Mode::Encrypted => Kind::Encrypted,
+        }
+    }
+}
+
+/// Asks the user how to resolve a conflict at the specified path.
+fn prompt<W>(dest: &Path, logger: &mut Logger<W>) -> io::Result<Strategy>
+where
+    W: Write,
+{
+    loop {
+        let question = format!(
+            "{} already exists. [b]ackup, [o]verwrite, [s]kip or [a]dopt? ",
+            dest.display()
+        );
+
+        // If there is no one to answer (e.g. the logger has no input, or it is
+        // closed), we play it safe.
+        let answer = match logger.ask(&question)? {
+            Some(answer) => answer,
+            None => return Ok(Strategy::Skip),
+        };
+
+        match answer.trim() {
+            "b" | "backup" => return Ok(Strategy::Backup),
+            "o" | "overwrite" => return Ok(Strategy::Overwrite),
+            "s" | "skip" => return Ok(Strategy::Skip),
+            "a" | "adopt" => return Ok(Strategy::Adopt),
+            _ => continue,
+        }
+    }
+}
+
+/// Removes a file, symlink or directory.
+fn remove(path: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+
+    if metadata.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Moves a file or directory, falling back to copying and removing it when the
+/// destination is on a different filesystem.
+///
+/// Nothing is ever moved over an existing file (e.g. an earlier backup); an
+/// error is returned instead.
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(to).is_ok() {
+        return Err(io::Error::new(
+            ErrorKind::AlreadyExists,
+            format!("{} already exists", to.display()),
+        ));
+    }
+
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    let mut options = CopyOptions::new();
+    options.copy_inside = true;
+
+    if from.is_dir() {
+        fs_extra::dir::move_dir(from, to, &options)
+    } else {
+        fs_extra::file::move_file(from, to, &fs_extra::file::CopyOptions::new())
+    }
+    .map(|_| ())
+    .map_err(|e| io::Error::other(e.to_string()))
+}
+
+/// Returns a path that nothing exists at yet: the path itself, or the path
+/// with a counter appended to its name (e.g. `.bashrc.1`).
+fn unused_path(path: &Path) -> PathBuf {
+    let mut unused = path.to_path_buf();
+    let mut counter = 0;
+
+    while fs::symlink_metadata(&unused).is_ok() {
+        counter += 1;
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{}", counter));
+        unused = path.with_file_name(name);
+    }
+
+    unused
+}
+
+/// Deploys dotfiles from the packages of a repository into a target directory.
+///
+/// # Fields
+///
+/// * `root` - The root directory of the dotfiles repository. Only links that
+///   point inside it are ever modified.
+/// * `target` - The directory to deploy the dotfiles into.
+/// * `fold` - Whether directories missing from the target are linked as a
+///   whole instead of being created and having their contents linked.
+/// * `conflict` - What to do with existing files that are in the way.
+/// * `backup_dir` - The directory that existing files are backed up to.
+/// * `manifest` - The record of deployed dotfiles, updated as links are made.
+/// * `mode` - How dotfiles are deployed by default.
+/// * `modes` - Deployment modes for specific files or directories (relative to
+///   the package they are in). A directory's mode applies to its contents.
+/// * `facts` - The facts about the current system, used to select alternates
+///   and render templates.
+/// * `variables` - The variables to render templates with.
+/// * `diff` - Whether to print a diff before replacing a copied or templated
+///   file with different contents.
+/// * `changes` - The number of dotfiles deployed (or directories unfolded) so
+///   far. Dotfiles that are already up to date aren't counted.
+/// * `skipped` - The number of dotfiles left undeployed so far because of an
+///   existing file that couldn't be replaced.
+/// * `package` - The package currently being linked.
+/// * `ignore` - The ignore rules of the package currently being linked.
+#[derive(Clone, Debug)]
+pub struct Linker {
+    pub root: PathBuf,
+    pub target: PathBuf,
+    pub fold: bool,
+    pub conflict: Strategy,
+    pub backup_dir: PathBuf,
+    pub manifest: Manifest,
+    pub mode: Mode,
+    pub modes: HashMap<PathBuf, Mode>,
+    pub facts: Facts,
+    pub variables: Map<String, Value>,
+    pub diff: bool,
+    pub changes: usize,
+    pub skipped: usize,
+    package: PathBuf,
+    ignore: Ignore,
+}
+
+impl Linker {
+    /// Creates a new linker that symlinks dotfiles with tree folding enabled
+    /// and the default conflict strategy.
+    ///
+    /// No facts are set, so only alternates with the `default` condition are
+    /// selected until `facts` is filled in.
+    ///
+    /// Backups are placed in a directory named after the current time inside
+    /// the `backups` directory of dotman's state directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The root directory of the dotfiles repository.
+    /// * `target` - The directory to deploy the dotfiles into.
+    /// * `manifest` - The record of deployed dotfiles to update.
+    pub fn new(root: &Path, target: &Path, manifest: Manifest) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            target: target.to_path_buf(),
+            fold: true,
+            conflict: Strategy::default(),
+            backup_dir: state_dir()
+                .join("backups")
+                .join(Local::now().format("%Y%m%d%H%M%S").to_string()),
+            manifest,
+            mode: Mode::default(),
+            modes: HashMap::new(),
+            facts: Facts::default(),
+            variables: Map::new(),
+            diff: false,
+            changes: 0,
+            skipped: 0,
+            package: root.to_path_buf(),
+            ignore: Ignore::default(),
+        }
+    }
+
+    /// Links the contents of a package directory into the target directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The package directory. Use the repository root to link the
+    ///   whole repository.
+    /// * `logger` - The logger to report each link to.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use dotman::dotfiles::link::{Linker, Mode};
+    /// use dotman::dotfiles::manifest::Manifest;
+    /// use dotman::logger::Logger;
+    /// use std::path::{Path, PathBuf};
+    ///
+    /// let root = Path::new("/home/johndoe/dotfiles");
+    /// let manifest = Manifest::load().unwrap();
+    /// let mut linker = Linker::new(root, Path::new("/home/johndoe"), manifest);
+    /// let mut logger = Logger::new();
+    ///
+    /// // Flatpak apps can't follow links out of their sandbox.
+    /// linker.modes.insert(PathBuf::from(".var/app"), Mode::Copy);
+    ///
+    /// linker.link(&root.join("vim"), &mut logger).unwrap();
+    /// linker.manifest.save().unwrap();
+    /// ```
+    pub fn link<W>(&mut self, package: &Path, logger: &mut Logger<W>) -> io::Result<()>
+    where
+        W: Write,
+    {
+        fs::create_dir_all(&self.target)?;
+        self.package = package.to_path_buf();
+        self.ignore = Ignore::load(&self.root, package)?;
+
+        let target = self.target.clone();
+        self.link_dir(package, &target, logger)
+    }
+
+    /// Deploys a single file or directory to the specified destination.
+    ///
+    /// Unlike `link`, the destination doesn't have to mirror the source's
+    /// location in a package. Modes are looked up relative to the repository
+    /// root.
+    ///
+    /// # Arguments
+    ///
+    /// * `src` - The file or directory in the repository.
+    /// * `dest` - The path to deploy it to.
+    /// * `logger` - The logger to report each link to.
+    pub fn deploy<W>(&mut self, src: &Path, dest: &Path, logger: &mut Logger<W>) -> io::Result<()>
+    where
+        W: Write,
+    {
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.package = self.root.clone();
+        self.ignore = Ignore::load(&self.root, &self.root)?;
+
+        self.link_entry(src, dest, logger)
+    }
+
+    /// Moves an existing file or directory from the target directory into a
+    /// package, at the same relative location, and links it back in its place.
+    /// Returns the path it was moved to.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The package directory to move the file into.
+    /// * `path` - The file or directory to adopt. It has to be inside the target
+    ///   directory.
+    /// * `logger` - The logger to report to.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use dotman::dotfiles::link::Linker;
+    /// use dotman::dotfiles::manifest::Manifest;
+    /// use dotman::logger::Logger;
+    /// use std::path::Path;
+    ///
+    /// let root = Path::new("/home/johndoe/dotfiles");
+    /// let manifest = Manifest::load().unwrap();
+    /// let mut linker = Linker::new(root, Path::new("/home/johndoe"), manifest);
+    /// let mut logger = Logger::new();
+    ///
+    /// // Moves the directory to /home/johndoe/dotfiles/nvim/.config/nvim.
+    /// let nvim = Path::new("/home/johndoe/.config/nvim");
+    /// linker.adopt(&root.join("nvim"), nvim, &mut logger).unwrap();
+    /// linker.manifest.save().unwrap();
+    /// ```
+    pub fn adopt<W>(
+        &mut self,
+        package: &Path,
+        path: &Path,
+        logger: &mut Logger<W>,
+    ) -> io::Result<PathBuf>
+    where
+        W: Write,
+    {
+        let rel = path
+            .strip_prefix(&self.target)
+            .map_err(|_| io::Error::other(format!("it is not inside {}", self.target.display())))?;
+
+        let metadata = fs::symlink_metadata(path)?;
+        if metadata.file_type().is_symlink() {
+            return Err(io::Error::other("symlinks cannot be adopted"));
+        }
+
+        // The path may be inside a directory that is already linked to the
+        // repository.
+        if path.canonicalize()?.starts_with(&self.root) {
+            return Err(io::Error::other("it is already in the repository"));
+        }
+
+        let src = package.join(rel);
+        if fs::symlink_metadata(&src).is_ok() {
+            return Err(io::Error::other(format!(
+                "{} already exists",
+                src.display()
+            )));
+        }
+
+        move_path(path, &src)?;
+        logger.info(&format!("Moved {} to {}", path.display(), src.display()));
+
+        symlink(&src, path)?;
+        logger.info(&format!("Linked {} -> {}", path.display(), src.display()));
+
+        self.manifest.record(Entry {
+            target: path.to_path_buf(),
+            source: src.clone(),
+            kind: Kind::Symlink,
+            backup: None,
+            hash: None,
+        });
+
+        Ok(src)
+    }
+
+    /// Returns the path of a source relative to the current package, without
+    /// any alternate conditions (i.e. the path it is deployed to).
+    fn rel_path(&self, src: &Path) -> PathBuf {
+        let rel = src.strip_prefix(&self.package).unwrap_or(src);
+        rel.iter().map(alternate::base).collect()
+    }
+
+    /// Returns the mode that a path in the current package is deployed with.
+    fn mode_of(&self, src: &Path) -> Mode {
+        let rel = self.rel_path(src);
+
+        if src.is_file() {
+            match rel.extension().and_then(|e| e.to_str()) {
+                Some(ENCRYPTED_EXTENSION) => return Mode::Encrypted,
+                Some(TEMPLATE_EXTENSION) => return Mode::Template,
+                _ => (),
+            }
+        }
+
+        // The most specific (i.e. longest) matching path wins.
+        self.modes
+            .iter()
+            .filter(|(path, _)| rel.starts_with(path))
+            .max_by_key(|(path, _)| path.components().count())
+            .map(|(_, mode)| *mode)
+            .unwrap_or(self.mode)
+    }
+
+    /// Returns a boolean indicating whether a directory can be deployed as a
+    /// single symlink, i.e. nothing inside it has to be deployed differently or
+    /// left out.
+    fn can_fold(&self, src: &Path) -> bool {
+        let rel = self.rel_path(src);
+
+        self.fold
+            && self.mode_of(src) == Mode::Symlink
+            && !self
+                .modes
+                .iter()
+                .any(|(path, mode)| *mode != Mode::Symlink && path.starts_with(&rel))
+            && !self.contains_generated(src)
+            && !self.contains_ignored(src)
+    }
+
+    /// Returns a boolean indicating whether a directory contains templates or
+    /// encrypted files, which have to be deployed individually.
+    fn contains_generated(&self, dir: &Path) -> bool {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return false,
+        };
+
+        entries.filter_map(Result::ok).any(|entry| {
+            let path = entry.path();
+
+            if path.is_dir() {
+                !self.ignore.is_ignored(&path, true) && self.contains_generated(&path)
+            } else {
+                self.mode_of(&path).extension().is_some()
+            }
+        })
+    }
+
+    /// Returns a boolean indicating whether a directory contains ignored files,
+    /// which would be reachable through a symlink to the directory.
+    fn contains_ignored(&self, dir: &Path) -> bool {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return false,
+        };
+
+        entries.filter_map(Result::ok).any(|entry| {
+            let path = entry.path();
+            let is_dir = path.is_dir();
+
+            self.ignore.is_ignored(&path, is_dir) || (is_dir && self.contains_ignored(&path))
+        })
+    }
+
+    /// Links every entry of a source directory into a destination directory.
+    fn link_dir<W>(&mut self, src: &Path, dest: &Path, logger: &mut Logger<W>) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+
+            if !self.ignore.is_ignored(&entry.path(), entry.path().is_dir()) {
+                names.push(entry.file_name());
+            }
+        }
+
+        // Sorting keeps the choice between equally specific alternates stable.
+        names.sort();
+
+        let mut entries = Vec::new();
+        for (name, dest_name) in alternate::select(names, &self.facts) {
+            let src = src.join(&name);
+            let mut dest = dest.join(&dest_name);
+
+            // Templates and encrypted files are deployed without their
+            // extension.
+            if let Some(extension) = self.mode_of(&src).extension() {
+                if Path::new(&dest_name)
+                    .extension()
+                    .is_some_and(|e| e == extension)
+                {
+                    dest.set_extension("");
+                }
+            }
+
+            entries.push((src, dest));
+        }
+
+        for (src, dest) in entries.iter() {
+            // Entries that are deployed to the same place (e.g. `.netrc` and
+            // `.netrc.age`) would replace each other on every run.
+            let others: Vec<String> = entries
+                .iter()
+                .filter(|(s, d)| d == dest && s != src)
+                .map(|(s, _)| s.display().to_string())
+                .collect();
+            if !others.is_empty() {
+                logger.warn(&format!(
+                    "Skipping {}: {} would be deployed to {} too.",
+                    src.display(),
+                    others.join(", "),
+                    dest.display()
+                ));
+                self.skipped += 1;
+                continue;
+            }
+
+            self.link_entry(src, dest, logger)?;
+        }
+
+        Ok(())
+    }
+
+    /// Links a single file or directory.
+    fn link_entry<W>(&mut self, src: &Path, dest: &Path, logger: &mut Logger<W>) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let metadata = match fs::symlink_metadata(dest) {
+            Ok(m) => m,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return self.place(src, dest, None, logger)
+            }
+            Err(e) => return Err(e),
+        };
+
+        if metadata.is_dir() && src.is_dir() {
+            return self.link_dir(src, dest, logger);
+        }
+
+        if self.is_deployed(src, dest)? {
+            return Ok(());
+        }
+
+        if metadata.file_type().is_symlink() {
+            let current = fs::read_link(dest)?;
+
+            // A directory folded from another package has to be unfolded
+            // before the contents of this package can be placed inside it.
+            if src.is_dir() && current.is_dir() && self.owns(&current) {
+                logger.info(&format!("Unfolding {}", dest.display()));
+                fs::remove_file(dest)?;
+                fs::create_dir(dest)?;
+                self.changes += 1;
+                self.manifest.record(Entry {
+                    target: dest.to_path_buf(),
+                    source: current.clone(),
+                    kind: Kind::Directory,
+                    backup: None,
+                    hash: None,
+                });
+                self.link_dir(&current, dest, logger)?;
+
+                if current == src {
+                    return Ok(());
+                }
+
+                return self.link_dir(src, dest, logger);
+            }
+        }
+
+        // Anything we deployed earlier that hasn't been touched since can
+        // safely be replaced (e.g. an outdated copy or a change of mode). A
+        // directory we created may have been given files of its own since,
+        // which must not be removed along with it.
+        if let Some(entry) = self.manifest.get(dest) {
+            if entry.is_intact()
+                && (entry.kind != Kind::Directory || self.holds_only_deployed(dest))
+            {
+                let backup = entry.backup.clone();
+                self.print_diff(src, dest, logger)?;
+                remove(dest)?;
+                return self.place(src, dest, backup, logger);
+            }
+
+            logger.warn(&format!(
+                "{} was changed after being deployed.",
+                dest.display()
+            ));
+        }
+
+        self.resolve(src, dest, logger)
+    }
+
+    /// Returns a boolean indicating whether everything inside a directory was
+    /// deployed by dotman and is still as it was deployed.
+    fn holds_only_deployed(&self, dir: &Path) -> bool {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return false,
+        };
+
+        entries.filter_map(Result::ok).all(|entry| {
+            let path = entry.path();
+
+            match self.manifest.get(&path) {
+                Some(e) if e.kind == Kind::Directory => self.holds_only_deployed(&path),
+                Some(e) => e.is_intact(),
+                None => false,
+            }
+        })
+    }
+
+    /// Returns a boolean indicating whether the destination is already an up
+    /// to date deployment of the source, making sure it is tracked in the
+    /// manifest if so.
+    fn is_deployed(&mut self, src: &Path, dest: &Path) -> io::Result<bool> {
+        let mode = self.mode_of(src);
+        let mut entry = Entry {
+            target: dest.to_path_buf(),
+            source: src.to_path_buf(),
+            kind: mode.into(),
+            backup: None,
+            hash: None,
+        };
+
+        let deployed = match mode {
+            // A folded directory has to be unfolded if something inside it
+            // now has to be deployed differently.
+            Mode::Symlink => (!src.is_dir() || self.can_fold(src)) && entry.is_intact(),
+            _ if src.is_file() => {
+                // Copies have to match what was deployed (no local changes)
+                // and what is in the repository (no upstream changes).
+                entry.hash = self.manifest.get(dest).and_then(|e| e.hash.clone());
+                let expected = hash_contents(&self.contents(src)?);
+
+                entry.is_intact() && entry.hash == Some(expected)
+            }
+            _ => false,
+        };
+
+        // Make sure dotfiles deployed before the manifest existed (or from a
+        // lost manifest) are still tracked.
+        if deployed {
+            self.manifest.record(entry);
+        }
+
+        Ok(deployed)
+    }
+
+    /// Deploys a source to a destination that is known to be free (folding the
+    /// whole directory if allowed) and records it in the manifest.
+    fn place<W>(
+        &mut self,
+        src: &Path,
+        dest: &Path,
+        backup: Option<PathBuf>,
+        logger: &mut Logger<W>,
+    ) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let mode = self.mode_of(src);
+        let mut entry = Entry {
+            target: dest.to_path_buf(),
+            source: src.to_path_buf(),
+            kind: mode.into(),
+            backup,
+            hash: None,
+        };
+
+        if src.is_dir() && !self.can_fold(src) {
+            fs::create_dir(dest)?;
+            entry.kind = Kind::Directory;
+            self.manifest.record(entry);
+            return self.link_dir(src, dest, logger);
+        }
+
+        match mode {
+            Mode::Copy if src.is_file() => {
+                fs::copy(src, dest)?;
+                logger.info(&format!("Copied {} to {}", src.display(), dest.display()));
+            }
+            Mode::Template if src.is_file() => {
+                fs::write(dest, self.contents(src)?)?;
+                logger.info(&format!("Rendered {} to {}", src.display(), dest.display()));
+            }
+            Mode::Encrypted if src.is_file() => {
+                let contents = self.contents(src)?;
+
+                // Secrets shouldn't be readable by anyone else, not even
+                // briefly. They're written next to the destination first so
+                // that a failed write doesn't leave a partial file behind.
+                let mut name = dest.file_name().unwrap_or_default().to_os_string();
+                name.push(".dotman-tmp");
+                let tmp = dest.with_file_name(name);
+
+                let written = fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .mode(0o600)
+                    .open(&tmp)
+                    .and_then(|mut file| file.write_all(&contents))
+                    .and_then(|_| fs::rename(&tmp, dest));
+                if let Err(e) = written {
+                    let _ = fs::remove_file(&tmp);
+                    return Err(e);
+                }
+                logger.info(&format!(
+                    "Decrypted {} to {}",
+                    src.display(),
+                    dest.display()
+                ));
+            }
+            Mode::Hardlink if src.is_file() => {
+                fs::hard_link(src, dest)?;
+                logger.info(&format!(
+                    "Hard linked {} -> {}",
+                    dest.display(),
+                    src.display()
+                ));
+            }
+            _ => {
+                symlink(src, dest)?;
+                entry.kind = Kind::Symlink;
+                logger.info(&format!("Linked {} -> {}", dest.display(), src.display()));
+            }
+        }
+
+        if entry.kind != Kind::Symlink {
+            entry.hash = Some(hash(dest)?);
+        }
+
+        self.manifest.record(entry);
+        self.changes += 1;
+
+        Ok(())
+    }
+
+    /// Resolves a conflict with an existing file at the destination using the
+    /// configured strategy.
+    fn resolve<W>(&mut self, src: &Path, dest: &Path, logger: &mut Logger<W>) -> io::Result<()>
+    where
+        W: Write,
+    {
+        // Adopting or skipping leaves the existing file's contents in place.
+        if !matches!(self.conflict, Strategy::Adopt | Strategy::Skip) {
+            self.print_diff(src, dest, logger)?;
+        }
+
+        let strategy = match self.conflict {
+            Strategy::Prompt => prompt(dest, logger)?,
+            strategy => strategy,
+        };
+        let mut backup = None;
+
+        match strategy {
+            Strategy::Backup => {
+                let rel = dest.strip_prefix(&self.target).unwrap_or(dest);
+                // Backups made within the same second share a directory, so
+                // the same file may already have been backed up to it.
+                let path = unused_path(&self.backup_dir.join(rel.strip_prefix("/").unwrap_or(rel)));
+
+                move_path(dest, &path)?;
+                logger.info(&format!(
+                    "Backed up {} to {}",
+                    dest.display(),
+                    path.display()
+                ));
+                backup = Some(path);
+            }
+
+            Strategy::Overwrite => {
+                remove(dest)?;
+                logger.warn(&format!("Overwrote {}", dest.display()));
+            }
+
+            Strategy::Adopt => {
+                // Adopting a symlink would just move the link into the
+                // repository, not the file it points to.
+                if fs::symlink_metadata(dest)?.file_type().is_symlink() {
+                    logger.warn(&format!(
+                        "Skipping {}: symlinks cannot be adopted.",
+                        dest.display()
+                    ));
+                    self.skipped += 1;
+                    return Ok(());
+                }
+
+                // The source of a template or an encrypted file isn't what gets
+                // deployed, so adopting would replace it with rendered output
+                // or plaintext (which would then be committed).
+                let generated = match self.mode_of(src) {
+                    Mode::Template | Mode::Encrypted => true,
+                    _ => src.is_dir() && self.contains_generated(src),
+                };
+                if generated {
+                    logger.warn(&format!(
+                        "Skipping {}: it cannot be adopted over a template or encrypted file.",
+                        dest.display()
+                    ));
+                    self.skipped += 1;
+                    return Ok(());
+                }
+
+                remove(src)?;
+                move_path(dest, src)?;
+                logger.info(&format!(
+                    "Adopted {} into {}",
+                    dest.display(),
+                    src.display()
+                ));
+            }
+
+            Strategy::Skip | Strategy::Prompt => {
+                logger.warn(&format!(
+                    "Skipping {}: a file already exists at the destination.",
+                    dest.display(),
+                ));
+                self.skipped += 1;
+                return Ok(());
+            }
+        }
+
+        self.place(src, dest, backup, logger)
+    }
+
+    /// Prints a diff between the destination and what the source would be
+    /// deployed as, if diffs are enabled and the source is copied or rendered
+    /// over an existing file.
+    fn print_diff<W>(&self, src: &Path, dest: &Path, logger: &mut Logger<W>) -> io::Result<()>
+    where
+        W: Write,
+    {
+        if !self.diff || !src.is_file() || !fs::symlink_metadata(dest)?.is_file() {
+            return Ok(());
+        }
+
+        let contents = match self.mode_of(src) {
+            Mode::Copy | Mode::Template | Mode::Encrypted => self.contents(src)?,
+            _ => return Ok(()),
+        };
+
+        // NOTE: Rendered templates may contain decrypted values, which the
+        // logger masks.
+        if let Some(diff) = status::diff(dest, &contents, src)? {
+            logger.print(&diff);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the contents that a file is deployed with (i.e. rendered or
+    /// decrypted if necessary).
+    fn contents(&self, src: &Path) -> io::Result<Vec<u8>> {
+        match self.mode_of(src) {
+            Mode::Template => {
+                let data = template::data(&self.facts, &self.variables);
+                Ok(template::render_file(src, &data)?.into_bytes())
+            }
+            Mode::Encrypted => crypt::decrypt_file(src),
+            _ => fs::read(src),
+        }
+    }
+
+    /// Returns a boolean indicating whether a path lies inside the dotfiles
+    /// repository.
+    fn owns(&self, path: &Path) -> bool {
+        path.starts_with(&self.root)
+    }
+}
+
+/// Removes a deployed entry from its target and restores the file it replaced
+/// (if it was backed up).
+///
+/// Entries that were changed after being deployed (e.g. a link that now points
+/// somewhere else) are left alone, as are directories that aren't empty.
+///
+/// # Arguments
+///
+/// * `entry` - The deployed entry, as recorded in the manifest.
+/// * `logger` - The logger to report to.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dotman::dotfiles::{link, manifest::Manifest};
+/// use dotman::logger::Logger;
+///
+/// let mut manifest = Manifest::load().unwrap();
+/// let mut logger = Logger::new();
+///
+/// // Undo the deployment in reverse order, so that links are removed before
+/// // the directories that hold them.
+/// while let Some(entry) = manifest.entries.pop() {
+///     link::unlink(&entry, &mut logger).unwrap();
+/// }
+/// manifest.save().unwrap();
+/// ```
+pub fn unlink<W>(entry: &Entry, logger: &mut Logger<W>) -> io::Result<()>
+where
+    W: Write,
+{
+    let target = &entry.target;
+
+    match entry.kind {
+        Kind::Directory => {
+            if let Err(e) = fs::remove_dir(target) {
+                if e.kind() != ErrorKind::NotFound {
+                    logger.warn(&format!(
+                        "Leaving {} alone: it is not empty.",
+                        target.display()
+                    ));
+                    return Ok(());
+                }
+            } else {
+                logger.info(&format!("Removed {}", target.display()));
+            }
+        }
+
+        _ if fs::symlink_metadata(target).is_err() => (),
+
+        _ if entry.is_intact() => {
+            fs::remove_file(target)?;
+            logger.info(&format!("Removed {}", target.display()));
+        }
+
+        _ => {
+            logger.warn(&format!(
+                "Leaving {} alone: it was changed after being deployed.",
+                target.display()
+            ));
+            return Ok(());
+        }
+    }
+
+    if let Some(backup) = &entry.backup {
+        if backup.exists() {
+            move_path(backup, target)?;
+            logger.info(&format!(
+                "Restored {} from {}",
+                target.display(),
+                backup.display()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes deployed entries whose source no longer exists in the repository
+/// (e.g. because the file was deleted from the repository) and returns the
+/// number of entries that were pruned.
+///
+/// Only entries deployed from the specified repository are considered. Pruned
+/// entries are removed from the manifest.
+///
+/// # Arguments
+///
+/// * `manifest` - The record of deployed dotfiles.
+/// * `root` - The root directory of the dotfiles repository.
+/// * `logger` - The logger to report each removal to.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dotman::dotfiles::{link, manifest::Manifest};
+/// use dotman::logger::Logger;
+/// use std::path::Path;
+///
+/// let mut manifest = Manifest::load().unwrap();
+/// let mut logger = Logger::new();
+///
+/// if link::prune(&mut manifest, Path::new("/home/johndoe/dotfiles"), &mut logger).unwrap() > 0 {
+///     manifest.save().unwrap();
+/// }
+/// ```
+pub fn prune<W>(manifest: &mut Manifest, root: &Path, logger: &mut Logger<W>) -> io::Result<usize>
+where
+    W: Write,
+{
+    let orphans: Vec<Entry> = manifest
+        .entries
+        .iter()
+        .rev()
+        .filter(|e| e.source.starts_with(root) && fs::symlink_metadata(&e.source).is_err())
+        .cloned()
+        .collect();
+
+    for entry in orphans.iter() {
+        logger.info(&format!(
+            "Pruning {}: {} no longer exists in the repository.",
+            entry.target.display(),
+            entry.source.display()
+        ));
+        unlink(entry, logger)?;
+        manifest.remove(&entry.target);
+    }
+
+    Ok(orphans.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A repository and a target directory to deploy it into, which are removed
+    /// when the fixture is dropped.
+    struct Fixture {
+        dir: TempDir,
+        root: PathBuf,
+        target: PathBuf,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let root = dir.path().join("dotfiles");
+            let target = dir.path().join("home");
+            fs::create_dir_all(&root).unwrap();
+            fs::create_dir_all(&target).unwrap();
+
+            Self { dir, root, target }
+        }
+
+        /// Writes a file in the repository, creating its parents.
+        fn write(&self, path: &str, contents: &str) {
+            let path = self.root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+
+        /// Returns a linker that keeps its backups inside the fixture.
+        fn linker(&self, manifest: Manifest) -> Linker {
+            let mut linker = Linker::new(&self.root, &self.target, manifest);
+            linker.backup_dir = self.dir.path().join("backups");
+            linker
+        }
+    }
+
+    fn logger() -> Logger<io::Sink> {
+        Logger::from(io::sink())
+    }
+
+    #[test]
+    fn later_backups_are_not_restored() {
+        let fixture = Fixture::new();
+        fixture.write("app/t.conf", "deployed\n");
+        let package = fixture.root.join("app");
+        let target = fixture.target.join("t.conf");
+
+        let mut linker = fixture.linker(Manifest::default());
+        linker.mode = Mode::Copy;
+        linker.link(&package, &mut logger()).unwrap();
+
+        // The copy is edited, so it is backed up when applying again.
+        fs::write(&target, "edited\n").unwrap();
+        let mut linker = fixture.linker(linker.manifest);
+        linker.mode = Mode::Copy;
+        linker.conflict = Strategy::Backup;
+        linker.link(&package, &mut logger()).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "deployed\n");
+
+        // Nothing was there before dotman, so nothing is restored.
+        for entry in linker.manifest.entries.iter().rev() {
+            unlink(entry, &mut logger()).unwrap();
+        }
+        assert!(fs::symlink_metadata(&target).is_err());
+    }
+
+    #[test]
+    fn directories_with_files_of_their_own_are_kept() {
+        let fixture = Fixture::new();
+        fixture.write("app/.config/tool/a.conf", "a\n");
+        let package = fixture.root.join("app");
+        let dir = fixture.target.join(".config/tool");
+
+        let mut linker = fixture.linker(Manifest::default());
+        linker.fold = false;
+        linker.link(&package, &mut logger()).unwrap();
+        fs::write(dir.join("mine.txt"), "mine\n").unwrap();
+
+        // The directory becomes a file in the repository.
+        fs::remove_dir_all(package.join(".config/tool")).unwrap();
+        fixture.write("app/.config/tool", "tool\n");
+
+        let mut linker = fixture.linker(linker.manifest);
+        linker.fold = false;
+        linker.conflict = Strategy::Backup;
+        linker.link(&package, &mut logger()).unwrap();
+
+        assert_eq!(fs::read_to_string(&dir).unwrap(), "tool\n");
+        let backup = linker.backup_dir.join(".config/tool/mine.txt");
+        assert_eq!(fs::read_to_string(backup).unwrap(), "mine\n");
+    }
+
+    /// Deploys `app/.bashrc` over an existing `.bashrc` with a strategy and
+    /// returns the linker, having answered any prompt with the input.
+    fn conflict(fixture: &Fixture, strategy: Strategy, input: &'static str) -> Linker {
+        fixture.write("app/.bashrc", "deployed\n");
+        fs::write(fixture.target.join(".bashrc"), "existing\n").unwrap();
+
+        let mut logger = Logger::from(Vec::new());
+        logger.set_input(io::Cursor::new(input));
+        let mut linker = fixture.linker(Manifest::default());
+        linker.conflict = strategy;
+        linker.link(&fixture.root.join("app"), &mut logger).unwrap();
+
+        linker
+    }
+
+    #[test]
+    fn directories_are_unfolded_for_other_packages() {
+        let fixture = Fixture::new();
+        fixture.write("nvim/.config/nvim/init.vim", "set number\n");
+        fixture.write("git/.config/git/config", "[user]\n");
+        let config = fixture.target.join(".config");
+
+        let mut linker = fixture.linker(Manifest::default());
+        linker
+            .link(&fixture.root.join("nvim"), &mut logger())
+            .unwrap();
+        assert_eq!(
+            fs::read_link(&config).unwrap(),
+            fixture.root.join("nvim/.config")
+        );
+
+        linker
+            .link(&fixture.root.join("git"), &mut logger())
+            .unwrap();
+        assert!(!fs::symlink_metadata(&config)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(
+            fs::read_link(config.join("nvim")).unwrap(),
+            fixture.root.join("nvim/.config/nvim")
+        );
+        assert_eq!(
+            fs::read_link(config.join("git")).unwrap(),
+            fixture.root.join("git/.config/git")
+        );
+        assert_eq!(linker.manifest.get(&config).unwrap().kind, Kind::Directory);
+    }
+
+    #[test]
+    fn applying_again_changes_nothing() {
+        let fixture = Fixture::new();
+        fixture.write("app/.bashrc", "bashrc\n");
+        fixture.write("app/.config/tool/a.conf", "a\n");
+        fixture.write("app/.gitconfig", "[user]\n");
+        let package = fixture.root.join("app");
+
+        let mut linker = fixture.linker(Manifest::default());
+        linker.modes.insert(PathBuf::from(".gitconfig"), Mode::Copy);
+        linker.link(&package, &mut logger()).unwrap();
+        assert_eq!(linker.changes, 3);
+
+        let modes = linker.modes.clone();
+        let mut linker = fixture.linker(linker.manifest);
+        linker.modes = modes;
+        linker.link(&package, &mut logger()).unwrap();
+        assert_eq!((linker.changes, linker.skipped), (0, 0));
+
+        for entry in linker.manifest.entries.iter() {
+            assert_eq!(status::state(entry), status::State::Ok);
+        }
+    }
+
+    #[test]
+    fn conflicts_are_backed_up() {
+        let fixture = Fixture::new();
+        let linker = conflict(&fixture, Strategy::Backup, "");
+        let target = fixture.target.join(".bashrc");
+        let backup = linker.backup_dir.join(".bashrc");
+
+        assert_eq!(
+            fs::read_link(&target).unwrap(),
+            fixture.root.join("app/.bashrc")
+        );
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "existing\n");
+        assert_eq!(linker.manifest.get(&target).unwrap().backup, Some(backup));
+    }
+
+    #[test]
+    fn conflicts_are_overwritten() {
+        let fixture = Fixture::new();
+        let linker = conflict(&fixture, Strategy::Overwrite, "");
+        let target = fixture.target.join(".bashrc");
+
+        assert_eq!(
+            fs::read_link(&target).unwrap(),
+            fixture.root.join("app/.bashrc")
+        );
+        assert!(!linker.backup_dir.exists());
+        assert_eq!(linker.manifest.get(&target).unwrap().backup, None);
+    }
+
+    #[test]
+    fn conflicts_are_skipped() {
+        let fixture = Fixture::new();
+        let linker = conflict(&fixture, Strategy::Skip, "");
+        let target = fixture.target.join(".bashrc");
+
+        assert_eq!(fs::read_to_string(&target).unwrap(), "existing\n");
+        assert_eq!((linker.changes, linker.skipped), (0, 1));
+        assert!(linker.manifest.get(&target).is_none());
+    }
+
+    #[test]
+    fn conflicts_are_adopted() {
+        let fixture = Fixture::new();
+        let linker = conflict(&fixture, Strategy::Adopt, "");
+        let source = fixture.root.join("app/.bashrc");
+
+        assert_eq!(fs::read_to_string(&source).unwrap(), "existing\n");
+        assert_eq!(
+            fs::read_link(fixture.target.join(".bashrc")).unwrap(),
+            source
+        );
+        assert_eq!(linker.changes, 1);
+    }
+
+    #[test]
+    fn conflicts_are_resolved_as_answered() {
+        let fixture = Fixture::new();
+        // Unknown answers are asked again.
+        conflict(&fixture, Strategy::Prompt, "x\no\n");
+        let target = fixture.target.join(".bashrc");
+        assert_eq!(
+            fs::read_link(&target).unwrap(),
+            fixture.root.join("app/.bashrc")
+        );
+
+        // Without an answer, nothing is replaced.
+        let fixture = Fixture::new();
+        let linker = conflict(&fixture, Strategy::Prompt, "");
+        let target = fixture.target.join(".bashrc");
+        assert_eq!(fs::read_to_string(&target).unwrap(), "existing\n");
+        assert_eq!(linker.skipped, 1);
+    }
+
+    #[test]
+    fn orphans_are_pruned() {
+        let fixture = Fixture::new();
+        fixture.write("app/.bashrc", "bashrc\n");
+        fixture.write("app/.vimrc", "set number\n");
+
+        let mut linker = fixture.linker(Manifest::default());
+        linker
+            .link(&fixture.root.join("app"), &mut logger())
+            .unwrap();
+        fs::remove_file(fixture.root.join("app/.vimrc")).unwrap();
+
+        let mut manifest = linker.manifest;
+        assert_eq!(
+            prune(&mut manifest, &fixture.root, &mut logger()).unwrap(),
+            1
+        );
+        assert!(fs::symlink_metadata(fixture.target.join(".vimrc")).is_err());
+        assert!(fixture.target.join(".bashrc").exists());
+        assert_eq!(manifest.entries.len(), 1);
+    }
+
+    #[test]
+    fn directories_with_ignored_files_are_not_folded() {
+        let fixture = Fixture::new();
+        fixture.write("app/.dotmanignore", "*.key\n");
+        fixture.write("app/.config/tool/tool.conf", "key_file = tool.key\n");
+        fixture.write("app/.config/tool/tool.key", "secret\n");
+        fixture.write("app/.config/other/other.conf", "other\n");
+        let config = fixture.target.join(".config");
+
+        let mut linker = fixture.linker(Manifest::default());
+        linker
+            .link(&fixture.root.join("app"), &mut logger())
+            .unwrap();
+
+        for dir in [&config, &config.join("tool")].iter() {
+            assert!(!fs::symlink_metadata(dir).unwrap().file_type().is_symlink());
+        }
+        assert_eq!(
+            fs::read_link(config.join("tool/tool.conf")).unwrap(),
+            fixture.root.join("app/.config/tool/tool.conf")
+        );
+        assert!(fs::symlink_metadata(config.join("tool/tool.key")).is_err());
+
+        // Directories without ignored files are still folded.
+        assert_eq!(
+            fs::read_link(config.join("other")).unwrap(),
+            fixture.root.join("app/.config/other")
+        );
+    }
+
+    #[test]
+    fn backups_made_within_a_second_are_all_kept() {
+        let fixture = Fixture::new();
+        let linker = conflict(&fixture, Strategy::Backup, "");
+        let target = fixture.target.join(".bashrc");
+
+        // Applying again within the same second uses the same backup directory.
+        fs::remove_file(&target).unwrap();
+        fs::write(&target, "replaced\n").unwrap();
+        let mut linker = fixture.linker(linker.manifest);
+        linker
+            .link(&fixture.root.join("app"), &mut logger())
+            .unwrap();
+
+        let backups = fixture.dir.path().join("backups");
+        assert_eq!(
+            fs::read_to_string(backups.join(".bashrc")).unwrap(),
+            "existing\n"
+        );
+        assert_eq!(
+            fs::read_to_string(backups.join(".bashrc.1")).unwrap(),
+            "replaced\n"
+        );
+        assert!(move_path(&backups.join(".bashrc.1"), &backups.join(".bashrc")).is_err());
+        assert_eq!(
+            fs::read_link(&target).unwrap(),
+            fixture.root.join("app/.bashrc")
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! Module for keeping track of the dotfiles that dotman has deployed.
+//!
+//! Every link (or directory) created in a target directory is recorded in a
+//! manifest stored in dotman's state directory, along with the backup of any
+//! file it replaced. This makes it possible to undo a deployment later on.
+extern crate serde_json;
+
+use super::state_dir;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    io::{self, BufReader, ErrorKind},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+/// Returns the SHA-256 hash of some contents as a hex string.
+///
+/// # Arguments
+///
+/// * `contents` - The contents to hash.
+pub fn hash_contents(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+/// Returns the SHA-256 hash of a file's contents as a hex string.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file.
+pub fn hash(path: &Path) -> io::Result<String> {
+    Ok(hash_contents(&fs::read(path)?))
+}
+
+/// Represents the kind of filesystem entry that was deployed.
+///
+/// # Variants
+///
+/// * `Symlink` - A symbolic link to a file or directory in the repository.
+/// * `Copy` - A copy of a file in the repository.
+/// * `Hardlink` - A hard link to a file in the repository.
+/// * `Template` - A file rendered from a template in the repository.
+/// * `Encrypted` - A file decrypted from an encrypted file in the repository.
+/// * `Directory` - A directory created to hold links (e.g. when unfolding).
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Symlink,
+    Copy,
+    Hardlink,
+    Template,
+    Encrypted,
+    Directory,
+}
+
+/// Represents a single deployed dotfile.
+///
+/// # Fields
+///
+/// * `target` - The deployed path (e.g. `~/.bashrc`).
+/// * `source` - The path in the repository that the target was deployed from.
+/// * `kind` - The kind of entry that was created at the target.
+/// * `backup` - The location of the file that was backed up to make way for
+///   the target, if any.
+/// * `hash` - The hash of the target's contents when it was deployed (only
+///   for copies, hard links, templates and decrypted files). Used to detect
+///   local changes.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Entry {
+    pub target: PathBuf,
+    pub source: PathBuf,
+    pub kind: Kind,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup: Option<PathBuf>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+impl Entry {
+    /// Returns a boolean indicating whether the target is still exactly as
+    /// dotman deployed it (i.e. it hasn't been removed, replaced or edited).
+    pub fn is_intact(&self) -> bool {
+        let metadata = match fs::symlink_metadata(&self.target) {
+            Ok(m) => m,
+            Err(_) => return false,
+        };
+
+        match self.kind {
+            Kind::Symlink => fs::read_link(&self.target)
+                .map(|current| current == self.source)
+                .unwrap_or(false),
+
+            Kind::Directory => metadata.is_dir(),
+
+            Kind::Copy | Kind::Hardlink | Kind::Template | Kind::Encrypted => {
+                if !metadata.is_file() {
+                    return false;
+                }
+
+                // Edits made through a hard link also show up in the
+                // repository, so a hard link is intact as long as it still
+                // shares its inode with the source.
+                if self.kind == Kind::Hardlink {
+                    if let Ok(source) = fs::metadata(&self.source) {
+                        if source.dev() == metadata.dev() && source.ino() == metadata.ino() {
+                            return true;
+                        }
+                    }
+                }
+
+                match (&self.hash, hash(&self.target)) {
+                    (Some(expected), Ok(actual)) => *expected == actual,
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+/// Represents the record of deployed dotfiles.
+///
+/// # Fields
+///
+/// * `entries` - The deployed entries, in the order they were created.
+/// * `path` - The file that the manifest is stored in.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Manifest {
+    pub entries: Vec<Entry>,
+
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Manifest {
+    /// Loads the manifest from dotman's state directory.
+    ///
+    /// An empty manifest is returned if nothing has been deployed yet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dotman::dotfiles::manifest::Manifest;
+    ///
+    /// let manifest = Manifest::load().unwrap();
+    /// for entry in manifest.entries.iter() {
+    ///     println!("{}", entry.target.display());
+    /// }
+    /// ```
+    pub fn load() -> io::Result<Self> {
+        Self::load_from(&state_dir().join("manifest.json"))
+    }
+
+    /// Loads a manifest from the specified file.
+    ///
+    /// An empty manifest is returned if the file does not exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file that the manifest is stored in.
+    pub fn load_from(path: &Path) -> io::Result<Self> {
+        let mut manifest = match fs::File::open(path) {
+            Ok(file) => serde_json::from_reader(BufReader::new(file))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(e),
+        };
+
+        manifest.path = path.to_path_buf();
+        Ok(manifest)
+    }
+
+    /// Writes the manifest back to the file it was loaded from.
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(&self.path, serde_json::to_string_pretty(self)?)
+    }
+
+    /// Returns the entry deployed at the specified target, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The deployed path.
+    pub fn get(&self, target: &Path) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.target == target)
+    }
+
+    /// Records a deployed entry.
+    ///
+    /// If an entry already exists for the same target, it is replaced in place,
+    /// keeping its backup, or the lack of one. Only the first record of a
+    /// target knows what was there before dotman, which is what undoing the
+    /// deployment has to restore; any later backup (e.g. of a local change to a
+    /// deployed copy) stays in the backup directory but isn't restored.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The deployed entry.
+    pub fn record(&mut self, mut entry: Entry) {
+        match self.entries.iter_mut().find(|e| e.target == entry.target) {
+            Some(existing) => {
+                entry.backup = existing.backup.take();
+                *existing = entry;
+            }
+            None => self.entries.push(entry),
+        }
+    }
+
+    /// Removes and returns the entry deployed at the specified target, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The deployed path.
+    pub fn remove(&mut self, target: &Path) -> Option<Entry> {
+        let index = self.entries.iter().position(|e| e.target == target)?;
+        Some(self.entries.remove(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(target: &str, backup: Option<&str>) -> Entry {
+        Entry {
+            target: PathBuf::from(target),
+            source: PathBuf::from("/dotfiles/app").join(target.trim_start_matches('/')),
+            kind: Kind::Symlink,
+            backup: backup.map(PathBuf::from),
+            hash: None,
+        }
+    }
+
+    #[test]
+    fn records_replace_entries_in_place() {
+        let mut manifest = Manifest::default();
+        manifest.record(entry("/home/.bashrc", Some("/backups/1/.bashrc")));
+        manifest.record(entry("/home/.vimrc", None));
+
+        let mut copy = entry("/home/.bashrc", Some("/backups/2/.bashrc"));
+        copy.kind = Kind::Copy;
+        manifest.record(copy);
+        manifest.record(entry("/home/.vimrc", Some("/backups/2/.vimrc")));
+
+        let targets: Vec<&Path> = manifest
+            .entries
+            .iter()
+            .map(|e| e.target.as_path())
+            .collect();
+        assert_eq!(
+            targets,
+            vec![Path::new("/home/.bashrc"), Path::new("/home/.vimrc")]
+        );
+
+        let bashrc = manifest.get(Path::new("/home/.bashrc")).unwrap();
+        assert_eq!(bashrc.kind, Kind::Copy);
+        assert_eq!(bashrc.backup, Some(PathBuf::from("/backups/1/.bashrc")));
+        assert_eq!(
+            manifest.get(Path::new("/home/.vimrc")).unwrap().backup,
+            None
+        );
+
+        assert!(manifest.remove(Path::new("/home/.bashrc")).is_some());
+        assert!(manifest.get(Path::new("/home/.bashrc")).is_none());
+    }
+
+    #[test]
+    fn manifests_are_saved_and_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state/manifest.json");
+
+        let mut manifest = Manifest::load_from(&path).unwrap();
+        assert!(manifest.entries.is_empty());
+        manifest.record(entry("/home/.bashrc", Some("/backups/1/.bashrc")));
+        manifest.save().unwrap();
+
+        let loaded = Manifest::load_from(&path).unwrap();
+        assert_eq!(loaded.entries.len(), 1);
+        assert_eq!(
+            loaded.entries[0].backup,
+            Some(PathBuf::from("/backups/1/.bashrc"))
+        );
+    }
+}
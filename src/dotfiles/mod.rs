@@ -1,9 +1,15 @@
 //! Module for working with dotfiles.
 
 use git2::{Error as GitError, Repository};
-use std::path::PathBuf;
+use shellexpand::tilde;
+use std::{env, path::PathBuf};
 
+pub mod alternate;
 pub mod git;
+pub mod ignore;
+pub mod link;
+pub mod manifest;
+pub mod status;
 
 /// Clones a dotfiles repository onto the local system.
 ///
@@ -15,3 +21,34 @@ pub mod git;
 pub fn clone(src: &str, dest: &PathBuf, force: bool) -> Result<Repository, GitError> {
     git::clone(src, dest, force)
 }
+
+/// Opens a dotfiles repository that already exists on the local system.
+///
+/// # Arguments
+///
+/// * `src` - The path to the local dotfiles repository.
+pub fn open(src: &PathBuf) -> Result<Repository, GitError> {
+    git::open(src)
+}
+
+/// Pulls the latest changes from the `origin` remote into a local dotfiles
+/// repository. Returns `true` if any changes were pulled.
+///
+/// # Arguments
+///
+/// * `repo` - The local dotfiles repository.
+pub fn sync(repo: &Repository) -> Result<bool, GitError> {
+    git::pull(repo, "origin")
+}
+
+/// Returns the directory that dotman keeps its local state (such as backups)
+/// in.
+///
+/// This is `$XDG_STATE_HOME/dotman`, falling back to `~/.local/state/dotman`
+/// if `XDG_STATE_HOME` is not set.
+pub fn state_dir() -> PathBuf {
+    match env::var("XDG_STATE_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir).join("dotman"),
+        _ => PathBuf::from(tilde("~/.local/state/dotman").as_ref()),
+    }
+}
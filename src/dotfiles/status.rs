@@ -0,0 +1,322 @@
+//! Module for comparing the deployed dotfiles against the repository they were
+//! deployed from.
+use super::git;
+use super::manifest::{hash, Entry, Kind, Manifest};
+use crate::{crypt, display, template};
+use git2::{Error as GitError, Repository};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Represents the state of a deployed dotfile.
+///
+/// # Variants
+///
+/// * `Ok` - The dotfile is deployed and up to date.
+/// * `Missing` - The dotfile was removed from the target.
+/// * `Replaced` - Something else (e.g. a link pointing elsewhere) is in place
+///   of the dotfile.
+/// * `Modified` - The deployed copy was edited locally.
+/// * `Outdated` - The dotfile changed in the repository since it was copied.
+/// * `Orphaned` - The dotfile no longer exists in the repository.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+    Ok,
+    Missing,
+    Replaced,
+    Modified,
+    Outdated,
+    Orphaned,
+}
+
+/// Represents the state of a single deployed dotfile.
+///
+/// # Fields
+///
+/// * `target` - The deployed path.
+/// * `source` - The path in the repository that the target was deployed from.
+/// * `kind` - The kind of entry that was deployed.
+/// * `state` - The state of the deployed entry.
+#[derive(Clone, Debug, Serialize)]
+pub struct DotfileStatus {
+    pub target: PathBuf,
+    pub source: PathBuf,
+    pub kind: Kind,
+    pub state: State,
+}
+
+/// Represents an uncommitted change in the repository's working directory.
+///
+/// # Fields
+///
+/// * `path` - The changed path, relative to the repository root.
+/// * `change` - What kind of change it is (e.g. `modified` or `untracked`).
+#[derive(Clone, Debug, Serialize)]
+pub struct Change {
+    pub path: String,
+    pub change: &'static str,
+}
+
+/// Represents the state of the dotfiles repository.
+///
+/// # Fields
+///
+/// * `changes` - The uncommitted changes in the working directory.
+/// * `ahead` - The number of local commits not pushed to the remote, if the
+///   current branch has an upstream branch.
+/// * `behind` - The number of remote commits not pulled yet, if the current
+///   branch has an upstream branch.
+#[derive(Clone, Debug, Serialize)]
+pub struct RepoStatus {
+    pub changes: Vec<Change>,
+    pub ahead: Option<usize>,
+    pub behind: Option<usize>,
+}
+
+/// Represents the drift between the repository and the deployed dotfiles.
+///
+/// # Fields
+///
+/// * `dotfiles` - The state of every dotfile deployed from the repository.
+/// * `repository` - The state of the repository itself.
+#[derive(Clone, Debug, Serialize)]
+pub struct Status {
+    pub dotfiles: Vec<DotfileStatus>,
+    pub repository: RepoStatus,
+}
+
+/// Returns the state of a deployed entry.
+///
+/// # Arguments
+///
+/// * `entry` - The deployed entry, as recorded in the manifest.
+pub fn state(entry: &Entry) -> State {
+    if fs::symlink_metadata(&entry.source).is_err() {
+        return State::Orphaned;
+    }
+
+    let metadata = match fs::symlink_metadata(&entry.target) {
+        Ok(m) => m,
+        Err(_) => return State::Missing,
+    };
+
+    if !entry.is_intact() {
+        // Only a regular file can be a locally edited copy; anything else has
+        // replaced the dotfile.
+        return match entry.kind {
+            Kind::Copy | Kind::Hardlink | Kind::Template | Kind::Encrypted
+                if metadata.is_file() =>
+            {
+                State::Modified
+            }
+            _ => State::Replaced,
+        };
+    }
+
+    // NOTE: Templates and encrypted files can only be compared against the
+    // repository by rendering or decrypting them again, which needs the
+    // config's variables or the key. They are only checked for local changes.
+    match entry.kind {
+        Kind::Copy | Kind::Hardlink => match (hash(&entry.target), hash(&entry.source)) {
+            (Ok(deployed), Ok(current)) if deployed == current => State::Ok,
+            _ => State::Outdated,
+        },
+        _ => State::Ok,
+    }
+}
+
+/// Compares the dotfiles deployed from a repository against the repository.
+///
+/// Directories created to hold dotfiles are not reported, since they are only
+/// a means of deploying their contents.
+///
+/// # Arguments
+///
+/// * `repo` - The local dotfiles repository.
+/// * `manifest` - The record of deployed dotfiles.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dotman::dotfiles::{self, manifest::Manifest, status};
+/// use std::path::Path;
+///
+/// let repo = dotfiles::open(&Path::new("/home/johndoe/dotfiles").to_path_buf()).unwrap();
+/// let manifest = Manifest::load().unwrap();
+///
+/// for dotfile in status::status(&repo, &manifest).unwrap().dotfiles {
+///     println!("{:?}: {}", dotfile.state, dotfile.target.display());
+/// }
+/// ```
+pub fn status(repo: &Repository, manifest: &Manifest) -> Result<Status, GitError> {
+    let root = repo.workdir().unwrap_or_else(|| Path::new(""));
+
+    let dotfiles = manifest
+        .entries
+        .iter()
+        .filter(|e| e.kind != Kind::Directory && e.source.starts_with(root))
+        .map(|e| DotfileStatus {
+            target: e.target.clone(),
+            source: e.source.clone(),
+            kind: e.kind,
+            state: state(e),
+        })
+        .collect();
+
+    let changes = git::changes(repo)?
+        .into_iter()
+        .map(|(path, change)| Change { path, change })
+        .collect();
+    let (ahead, behind) = match git::ahead_behind(repo)? {
+        Some((ahead, behind)) => (Some(ahead), Some(behind)),
+        None => (None, None),
+    };
+
+    Ok(Status {
+        dotfiles,
+        repository: RepoStatus {
+            changes,
+            ahead,
+            behind,
+        },
+    })
+}
+
+/// Returns a colored unified diff between a deployed file and the contents it
+/// would be replaced with, or `None` if they are the same.
+///
+/// # Arguments
+///
+/// * `target` - The deployed file.
+/// * `contents` - The new contents of the file.
+/// * `source` - The path in the repository that the new contents come from.
+pub fn diff(target: &Path, contents: &[u8], source: &Path) -> io::Result<Option<String>> {
+    let current = fs::read(target)?;
+    if current == contents {
+        return Ok(None);
+    }
+
+    let diff = match (std::str::from_utf8(&current), std::str::from_utf8(contents)) {
+        (Ok(old), Ok(new)) => display::diff(
+            old,
+            new,
+            &target.display().to_string(),
+            &source.display().to_string(),
+        ),
+        _ => format!(
+            "Binary files {} and {} differ\n",
+            target.display(),
+            source.display()
+        ),
+    };
+
+    Ok(Some(diff))
+}
+
+/// Returns a diff of the changes that deploying an entry again would make to
+/// its target, or `None` if there are none.
+///
+/// Only copies, templates and encrypted files are compared, since symlinks and
+/// hard links always have the same contents as their source.
+///
+/// # Arguments
+///
+/// * `entry` - The deployed entry, as recorded in the manifest.
+/// * `data` - The data to render templates with (see `template::data`).
+///
+/// # Examples
+///
+/// ```no_run
+/// use dotman::dotfiles::{manifest::Manifest, status};
+/// use dotman::facts::Facts;
+/// use dotman::template;
+///
+/// let data = template::data(&Facts::gather(), &serde_json::Map::new());
+///
+/// for entry in Manifest::load().unwrap().entries.iter() {
+///     if let Some(diff) = status::pending(entry, &data).unwrap() {
+///         print!("{}", diff);
+///     }
+/// }
+/// ```
+pub fn pending(entry: &Entry, data: &Value) -> io::Result<Option<String>> {
+    if !entry.target.is_file() || !entry.source.is_file() {
+        return Ok(None);
+    }
+
+    let contents = match entry.kind {
+        Kind::Copy => fs::read(&entry.source)?,
+        Kind::Template => template::render_file(&entry.source, data)?.into_bytes(),
+        Kind::Encrypted => crypt::decrypt_file(&entry.source)?,
+        _ => return Ok(None),
+    };
+
+    diff(&entry.target, &contents, &entry.source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    /// Returns an entry for a file deployed from a temporary directory, which
+    /// holds both the source and the target.
+    fn entry(dir: &Path, kind: Kind) -> Entry {
+        let source = dir.join("source");
+        let target = dir.join("target");
+        fs::write(&source, "deployed\n").unwrap();
+
+        let hash = match kind {
+            Kind::Symlink => {
+                symlink(&source, &target).unwrap();
+                None
+            }
+            _ => {
+                fs::copy(&source, &target).unwrap();
+                Some(hash(&target).unwrap())
+            }
+        };
+
+        Entry {
+            target,
+            source,
+            kind,
+            backup: None,
+            hash,
+        }
+    }
+
+    #[test]
+    fn deployed_links_are_compared() {
+        let dir = tempfile::tempdir().unwrap();
+        let entry = entry(dir.path(), Kind::Symlink);
+        assert_eq!(state(&entry), State::Ok);
+
+        fs::remove_file(&entry.target).unwrap();
+        symlink(dir.path(), &entry.target).unwrap();
+        assert_eq!(state(&entry), State::Replaced);
+
+        fs::remove_file(&entry.target).unwrap();
+        assert_eq!(state(&entry), State::Missing);
+
+        fs::remove_file(&entry.source).unwrap();
+        assert_eq!(state(&entry), State::Orphaned);
+    }
+
+    #[test]
+    fn deployed_copies_are_compared() {
+        let dir = tempfile::tempdir().unwrap();
+        let entry = entry(dir.path(), Kind::Copy);
+        assert_eq!(state(&entry), State::Ok);
+
+        fs::write(&entry.source, "changed upstream\n").unwrap();
+        assert_eq!(state(&entry), State::Outdated);
+
+        fs::write(&entry.target, "changed locally\n").unwrap();
+        assert_eq!(state(&entry), State::Modified);
+    }
+}
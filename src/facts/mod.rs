@@ -0,0 +1,82 @@
+//! Module for gathering facts about the local system.
+use git2::Config as GitConfig;
+use serde::Serialize;
+use std::{env, fs};
+
+/// Returns the ID of the Linux distribution (e.g. `ubuntu` or `arch`) as
+/// specified in `/etc/os-release`.
+fn distro() -> Option<String> {
+    let os_release = fs::read_to_string("/etc/os-release").ok()?;
+
+    os_release
+        .lines()
+        .find_map(|line| line.strip_prefix("ID="))
+        .map(|id| id.trim_matches('"').to_string())
+}
+
+/// Returns the user's email address as configured in Git.
+fn email() -> Option<String> {
+    GitConfig::open_default()
+        .and_then(|config| config.get_string("user.email"))
+        .ok()
+}
+
+/// Returns the hostname of the system.
+fn hostname() -> Option<String> {
+    // NOTE: Like `shell::is_root`, we rely on the `/proc` filesystem here.
+    let hostname = fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| env::var("HOSTNAME").ok())?;
+
+    Some(hostname.trim().to_string())
+}
+
+/// Represents facts about the system that dotman is running on.
+///
+/// # Fields
+///
+/// * `hostname` - The hostname of the system.
+/// * `user` - The name of the current user.
+/// * `os` - The operating system (e.g. `linux` or `macos`).
+/// * `distro` - The ID of the Linux distribution (e.g. `ubuntu`), if any.
+/// * `arch` - The CPU architecture (e.g. `x86_64`).
+/// * `email` - The user's email address as configured in Git, if any.
+/// * `class` - A user-defined class for the system (e.g. `work`), if any.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Facts {
+    pub hostname: String,
+    pub user: String,
+    pub os: String,
+    pub distro: Option<String>,
+    pub arch: String,
+    pub email: Option<String>,
+    pub class: Option<String>,
+}
+
+impl Facts {
+    /// Gathers facts about the current system.
+    ///
+    /// The class is not a property of the system and has to be set separately.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dotman::facts;
+    ///
+    /// let mut facts = facts::Facts::gather();
+    /// facts.class = Some(String::from("work"));
+    ///
+    /// println!("Running on {} ({})", facts.hostname, facts.os);
+    /// ```
+    pub fn gather() -> Self {
+        Self {
+            hostname: hostname().unwrap_or_default(),
+            user: env::var("USER").unwrap_or_default(),
+            os: env::consts::OS.to_string(),
+            distro: distro(),
+            arch: env::consts::ARCH.to_string(),
+            email: email(),
+            class: None,
+        }
+    }
+}
@@ -0,0 +1,335 @@
+//! dotman is a dotfiles manager. This crate is the library that the `dotman`
+//! binary is built on, for tools that want to deploy dotfiles themselves.
+//!
+//! A config is applied in four steps:
+//!
+//! 1. `load` the config of a dotfiles repository.
+//! 2. `plan` the tasks to run, which checks the whole config first.
+//! 3. `apply` the plan.
+//! 4. `report` how the tasks went.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use dotman::{config::Context, logger::Logger, Registry, Stats};
+//! use std::path::Path;
+//!
+//! let root = Path::new("/home/johndoe/dotfiles");
+//! let mut logger = Logger::new();
+//! let registry = Registry::default();
+//!
+//! let config = dotman::load(root, None, None).unwrap();
+//! let context = Context::new(root, &config);
+//!
+//! match dotman::plan(&config, &registry, &context) {
+//!     Ok(plan) => {
+//!         let outcomes = dotman::apply(&plan, &registry, &context, &mut logger);
+//!         dotman::report(&Stats::new(&outcomes), &mut logger);
+//!     }
+//!     Err(problems) => {
+//!         for problem in problems.iter() {
+//!             logger.error(problem);
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Library code never exits the process; errors are returned (or logged, for
+//! the output of tasks) and what to do about them is left to the caller.
+//! Everything is written through the caller's logger, which is also what the
+//! user is asked through when a task needs an answer (see `Logger::ask`).
+pub mod config;
+pub mod consts;
+pub mod crypt;
+pub mod display;
+pub mod dotfiles;
+pub mod facts;
+pub mod logger;
+pub mod shell;
+pub mod template;
+
+pub use config::{
+    Config, ConfigError, Context, Format, Module, Outcome, Registry, Stats, Status, Task,
+};
+
+use logger::Logger;
+use std::{io::Write, path::Path};
+
+/// Represents the tasks that applying a config would run.
+///
+/// # Fields
+///
+/// * `steps` - The tasks, in the order that they are run.
+#[derive(Debug)]
+pub struct Plan<'a> {
+    pub steps: Vec<Step<'a>>,
+}
+
+/// Represents a task in a plan.
+///
+/// # Fields
+///
+/// * `task` - The task.
+/// * `pending` - Whether the task has anything to do, as far as its module
+///   could tell when the plan was made (see `Module::check`). Tasks that
+///   aren't pending are checked again right before they would be run, and are
+///   reported as up to date if they still have nothing to do.
+#[derive(Debug)]
+pub struct Step<'a> {
+    pub task: &'a Task,
+    pub pending: bool,
+}
+
+/// Loads the config of a dotfiles repository.
+///
+/// # Arguments
+///
+/// * `root` - The root directory of the dotfiles repository.
+/// * `file` - The config file, relative to `root`. If not specified, the
+///   repository is searched for one (see `config::find`).
+/// * `format` - The format of the config file. If not specified, it is
+///   determined from the file's extension.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dotman::Format;
+/// use std::path::Path;
+///
+/// let root = Path::new("/home/johndoe/dotfiles");
+/// let config = dotman::load(root, Some(Path::new("work.conf")), Some(Format::Yaml)).unwrap();
+/// ```
+pub fn load(
+    root: &Path,
+    file: Option<&Path>,
+    format: Option<Format>,
+) -> Result<Config, Box<ConfigError>> {
+    let file = match file {
+        Some(file) => root.join(file),
+        None => config::find(root).ok_or_else(|| {
+            Box::new(ConfigError::new(
+                root,
+                "No config file found in the dotfiles repository",
+            ))
+        })?,
+    };
+
+    config::parse(file, format)
+}
+
+/// Checks a config and returns the tasks that applying it would run, or every
+/// problem with it. Nothing is applied.
+///
+/// Each task's module checks whether the task has anything to do (see
+/// `Module::check`), which may run commands of its own (e.g. a command task's
+/// `unless` guard). This happens once per task, before any task is applied, so
+/// the checks see the system as it was before applying the plan; `apply` checks
+/// the tasks that aren't pending again, since an earlier task may have undone
+/// their state. Use `Config::check` to only look for problems.
+///
+/// # Arguments
+///
+/// * `config` - The config to plan.
+/// * `registry` - The modules that the tasks can use.
+/// * `context` - The environment that the tasks would be run in.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dotman::{config::Context, Registry};
+/// use std::{io::Stdout, path::Path};
+///
+/// let root = Path::new("/home/johndoe/dotfiles");
+/// let registry = Registry::<Stdout>::default();
+/// let config = dotman::load(root, None, None).unwrap();
+/// let context = Context::new(root, &config);
+///
+/// let plan = dotman::plan(&config, &registry, &context).unwrap();
+/// for step in plan.steps.iter().filter(|step| step.pending) {
+///     println!("{} would run.", step.task.name);
+/// }
+/// ```
+pub fn plan<'a, W>(
+    config: &'a Config,
+    registry: &Registry<W>,
+    context: &Context,
+) -> Result<Plan<'a>, Vec<String>>
+where
+    W: Write,
+{
+    let problems = config.check(registry, context);
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+
+    let steps = config
+        .tasks()
+        .iter()
+        .map(|task| Step {
+            task,
+            pending: !registry.check(&task.module, &task.args, context),
+        })
+        .collect();
+
+    Ok(Plan { steps })
+}
+
+/// Runs the tasks in a plan and returns how each of them went, in the same
+/// order. A banner is logged as each task starts, and its captured output and
+/// outcome (along with how long it took) as it finishes.
+///
+/// Tasks that weren't pending when the plan was made are checked again right
+/// before they would be run, and run if an earlier task undid their state.
+///
+/// # Arguments
+///
+/// * `plan` - The plan to apply (see `plan`).
+/// * `registry` - The modules that the tasks can use.
+/// * `context` - The environment to run the tasks in.
+/// * `logger` - The logger to write task output to.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dotman::{config::Context, logger::Logger, Registry, Status};
+/// use std::path::Path;
+///
+/// let root = Path::new("/home/johndoe/dotfiles");
+/// let mut logger = Logger::new();
+/// let registry = Registry::default();
+/// let config = dotman::load(root, None, None).unwrap();
+/// let context = Context::new(root, &config);
+///
+/// let plan = dotman::plan(&config, &registry, &context).unwrap();
+/// let outcomes = dotman::apply(&plan, &registry, &context, &mut logger);
+///
+/// for (step, outcome) in plan.steps.iter().zip(outcomes.iter()) {
+///     if outcome.status == Status::Changed {
+///         println!("{} changed something.", step.task.name);
+///     }
+/// }
+/// ```
+pub fn apply<W>(
+    plan: &Plan,
+    registry: &Registry<W>,
+    context: &Context,
+    logger: &mut Logger<W>,
+) -> Vec<Outcome>
+where
+    W: Write,
+{
+    if plan.steps.is_empty() {
+        logger.warn("No tasks specified.");
+    }
+
+    let mut outcomes = Vec::new();
+
+    for step in plan.steps.iter() {
+        let task = step.task;
+        logger.print(&display::banner(
+            &format!("TASK: {}", &task.name),
+            None,
+            None,
+        ));
+
+        // NOTE: Earlier tasks may have undone the state that a task was
+        // planned against (e.g. removed the file it creates).
+        let pending = step.pending || !registry.check(&task.module, &task.args, context);
+        let outcome = if pending {
+            registry.run(&task.module, &task.args, context, logger)
+        } else {
+            Outcome::from(Status::Ok)
+        };
+        if let Some(output) = outcome.output.as_deref().filter(|o| !o.is_empty()) {
+            // NOTE: Output may contain secrets, which the logger masks.
+            logger.print(output);
+        }
+
+        let took = outcome
+            .duration
+            .map(|d| format!(" ({:.2}s)", d.as_secs_f64()))
+            .unwrap_or_default();
+        match &outcome.status {
+            Status::Ok => logger.success(&format!("Already up to date.{}", took)),
+            Status::Changed => logger.success(&format!("Done.{}", took)),
+            Status::Skipped => logger.warn(&format!("Skipped.{}", took)),
+            Status::Failed(e) => logger.error(&format!("{}{}", e, took)),
+        };
+        logger.print("\n");
+
+        outcomes.push(outcome);
+    }
+
+    outcomes
+}
+
+/// Writes how the tasks of a config went.
+///
+/// # Arguments
+///
+/// * `stats` - The stats of the outcomes returned by `apply` (see
+///   `Stats::new`).
+/// * `logger` - The logger to write the stats to.
+pub fn report<W>(stats: &Stats, logger: &mut Logger<W>)
+where
+    W: Write,
+{
+    logger.print(&display::stats(stats));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, io};
+
+    #[test]
+    fn tasks_undone_by_earlier_tasks_are_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("marker");
+        fs::write(&marker, "").unwrap();
+
+        let config = serde_json::json!({
+            "tasks": [
+                {
+                    "name": "Remove the marker",
+                    "module": "command",
+                    "args": format!("rm {}", marker.display())
+                },
+                {
+                    "name": "Create the marker",
+                    "module": "command",
+                    "args": {
+                        "command": format!("touch {}", marker.display()),
+                        "creates": marker.display().to_string()
+                    }
+                },
+                {
+                    "name": "Check the marker",
+                    "module": "command",
+                    "args": {
+                        "command": "false",
+                        "creates": marker.display().to_string()
+                    }
+                }
+            ]
+        });
+        fs::write(dir.path().join("dotman.json"), config.to_string()).unwrap();
+
+        let registry = Registry::<io::Sink>::default();
+        let config = load(dir.path(), None, None).unwrap();
+        let context = Context::new(dir.path(), &config);
+        let plan = plan(&config, &registry, &context).unwrap();
+        assert_eq!(
+            plan.steps.iter().map(|s| s.pending).collect::<Vec<_>>(),
+            vec![true, false, false]
+        );
+
+        let outcomes = apply(&plan, &registry, &context, &mut Logger::from(io::sink()));
+        let statuses: Vec<&Status> = outcomes.iter().map(|o| &o.status).collect();
+        assert_eq!(
+            statuses,
+            vec![&Status::Changed, &Status::Changed, &Status::Ok]
+        );
+        assert!(marker.exists());
+    }
+}
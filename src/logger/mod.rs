@@ -4,10 +4,8 @@ extern crate chrono;
 
 use ansi_term::{Color, Style};
 use chrono::Local;
-use std::{
-    io::{self, Stdout, Write},
-    process,
-};
+use regex::{Captures, Regex};
+use std::io::{self, BufRead, Stdout, Write};
 
 /// Returns the current time in the format H:M:S (Hours, Minutes and Seconds)
 fn get_fmt_time() -> String {
@@ -15,23 +13,59 @@ fn get_fmt_time() -> String {
     format!("{}", date.format("%H:%M:%S"))
 }
 
+/// Returns the text of a pattern match with its capture groups (or the whole
+/// match if there are none) replaced by `****`.
+fn mask_groups(caps: &Captures) -> String {
+    let whole = caps.get(0).unwrap();
+    if caps.len() == 1 {
+        return String::from("****");
+    }
+
+    let text = whole.as_str();
+    let mut masked = String::new();
+    let mut last = 0;
+
+    for group in caps.iter().skip(1).flatten() {
+        let (start, end) = (group.start() - whole.start(), group.end() - whole.start());
+
+        // Nested groups are already masked along with the outer group.
+        if start < last {
+            continue;
+        }
+
+        masked.push_str(&text[last..start]);
+        masked.push_str("****");
+        last = end;
+    }
+
+    masked.push_str(&text[last..]);
+    masked
+}
+
 /// The logger object.
 ///
 /// # Fields
 ///
 /// * `target` - The target for the logger to write to.
+/// * `secrets` - The values to mask in every message (e.g. decrypted tokens).
+/// * `patterns` - The patterns of text to mask in every message.
+/// * `input` - Where the answers to questions are read from, if anywhere.
 pub struct Logger<W>
 where
     W: Write,
 {
     target: W,
+    secrets: Vec<String>,
+    patterns: Vec<Regex>,
+    input: Option<Box<dyn BufRead>>,
 }
 
 impl<W> Logger<W>
 where
     W: Write,
 {
-    /// Creates a new logger that writes to the specified target.
+    /// Creates a new logger that writes to the specified target. It has no
+    /// input to read answers from (see `set_input`).
     ///
     /// # Arguments
     ///
@@ -41,22 +75,240 @@ where
     ///
     /// Creating a new logger that writes to stderr:
     /// ```
-    /// use logger::Logger;
-    /// use std::io::prelude::*;
+    /// use dotman::logger::Logger;
     ///
-    /// let mut logger = Logger::from(Box::new(std::io::stderr()));
+    /// let mut logger = Logger::from(std::io::stderr());
     /// ```
     ///
     /// Creating a new logger that writes to a file:
-    /// ```
-    /// use logger::Logger;
-    /// use std::io::prelude::*;
+    /// ```no_run
+    /// use dotman::logger::Logger;
     ///
     /// let out = std::fs::File::create("/home/johndoe/log.txt").unwrap();
     /// let mut logger = Logger::from(out);
     /// ```
     pub fn from(target: W) -> Self {
-        Self { target }
+        Self {
+            target,
+            secrets: Vec::new(),
+            patterns: Vec::new(),
+            input: None,
+        }
+    }
+
+    /// Returns the target that the logger writes to, e.g. to read what was
+    /// written to a buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dotman::logger::Logger;
+    ///
+    /// let mut logger = Logger::from(Vec::new());
+    /// logger.print("Hello!");
+    ///
+    /// assert_eq!(logger.target(), b"Hello!");
+    /// ```
+    pub fn target(&self) -> &W {
+        &self.target
+    }
+
+    /// Sets where the answers to questions (see `ask`) are read from.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input to read answers from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dotman::logger::Logger;
+    ///
+    /// let mut logger = Logger::from(Vec::new());
+    /// logger.set_input(std::io::Cursor::new("yes\n"));
+    ///
+    /// assert_eq!(logger.ask("Continue? ").unwrap().as_deref(), Some("yes"));
+    /// ```
+    pub fn set_input<R>(&mut self, input: R)
+    where
+        R: BufRead + 'static,
+    {
+        self.input = Some(Box::new(input));
+    }
+
+    /// Writes a question to the target and returns the answer read from the
+    /// input, without the trailing newline.
+    ///
+    /// Nothing is written if the logger has no input. `None` is returned then,
+    /// or if the input has ended, so that the caller can fall back to a safe
+    /// default.
+    ///
+    /// # Arguments
+    ///
+    /// * `question` - The question to write. A trailing newline isn't added.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dotman::logger::Logger;
+    ///
+    /// let mut logger = Logger::from(Vec::new());
+    /// assert_eq!(logger.ask("Continue? ").unwrap(), None);
+    /// ```
+    pub fn ask(&mut self, question: &str) -> io::Result<Option<String>> {
+        if self.input.is_none() {
+            return Ok(None);
+        }
+
+        self.print(question);
+        self.target.flush()?;
+
+        let mut answer = String::new();
+        let read = match self.input.as_mut() {
+            Some(input) => input.read_line(&mut answer)?,
+            None => 0,
+        };
+        if read == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(answer.trim_end_matches(&['\r', '\n'][..]).to_string()))
+    }
+
+    /// Adds a value that must never be written, such as a decrypted password.
+    /// It is masked as `****` in every message written from now on.
+    ///
+    /// # Arguments
+    ///
+    /// * `secret` - The value to mask.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dotman::logger::Logger;
+    ///
+    /// let mut logger = Logger::from(Vec::new());
+    /// logger.add_secret("hunter");
+    /// logger.add_secret("hunter2");
+    ///
+    /// logger.info("Logging in with password hunter2");
+    /// let out = String::from_utf8_lossy(logger.target());
+    /// assert!(out.ends_with(": Logging in with password ****\n"));
+    /// ```
+    pub fn add_secret(&mut self, secret: &str) {
+        if secret.is_empty() || self.secrets.iter().any(|s| s == secret) {
+            return;
+        }
+
+        self.secrets.push(secret.to_string());
+
+        // Longer secrets are masked first, so that a secret containing another
+        // one is not partially revealed.
+        self.secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    }
+
+    /// Adds a regular expression matching text that must never be written. It
+    /// is masked as `****` in every message written from now on.
+    ///
+    /// If the pattern has capture groups, only the groups are masked, so that
+    /// the surrounding text is kept for context.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The regular expression to mask.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dotman::logger::Logger;
+    ///
+    /// let mut logger = Logger::from(Vec::new());
+    /// logger.add_pattern(r"--token[= ](\S+)").unwrap();
+    ///
+    /// logger.info("Running command: gh auth login --token ghp_16C7e42F292c6912E7710c8");
+    /// let out = String::from_utf8_lossy(logger.target());
+    /// assert!(out.ends_with(": Running command: gh auth login --token ****\n"));
+    /// ```
+    ///
+    /// Patterns without capture groups mask the whole match:
+    /// ```
+    /// use dotman::logger::Logger;
+    ///
+    /// let mut logger = Logger::from(Vec::new());
+    /// logger.add_pattern(r"ghp_\w+").unwrap();
+    ///
+    /// logger.print("export GITHUB_TOKEN=ghp_16C7e42F292c6912E7710c8\n");
+    /// assert_eq!(logger.target(), b"export GITHUB_TOKEN=****\n");
+    /// ```
+    ///
+    /// Nested groups are masked along with the group that contains them:
+    /// ```
+    /// use dotman::logger::Logger;
+    ///
+    /// let mut logger = Logger::from(Vec::new());
+    /// logger.add_pattern(r"(\w+)@((\w+)\.com)").unwrap();
+    ///
+    /// logger.print("Signed in as johndoe@example.com.");
+    /// assert_eq!(logger.target(), b"Signed in as ****@****.");
+    /// ```
+    pub fn add_pattern(&mut self, pattern: &str) -> Result<(), regex::Error> {
+        self.patterns.push(Regex::new(pattern)?);
+        Ok(())
+    }
+
+    /// Returns a message with all secrets and patterns masked, for text that is
+    /// displayed without going through the logger (e.g. in a pager).
+    ///
+    /// # Arguments
+    ///
+    /// * `msg` - The message to mask.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dotman::logger::Logger;
+    ///
+    /// let mut logger = Logger::new();
+    /// logger.add_secret("hunter2");
+    /// logger.add_pattern(r"user=(\w+)").unwrap();
+    ///
+    /// assert_eq!(logger.mask("+password=hunter2"), "+password=****");
+    /// assert_eq!(logger.mask("+user=johndoe"), "+user=****");
+    /// assert_eq!(logger.mask("+set number"), "+set number");
+    /// ```
+    pub fn mask(&self, msg: &str) -> String {
+        let mut msg = msg.to_string();
+
+        for secret in self.secrets.iter() {
+            msg = msg.replace(secret.as_str(), "****");
+        }
+
+        for pattern in self.patterns.iter() {
+            msg = pattern
+                .replace_all(&msg, |caps: &Captures| mask_groups(caps))
+                .into_owned();
+        }
+
+        msg
+    }
+
+    /// Writes text to the target as it is (i.e. without a label or timestamp),
+    /// with all secrets and patterns masked. Used for output such as diffs.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text to write. A trailing newline isn't added.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dotman::logger::Logger;
+    ///
+    /// let mut logger = Logger::new();
+    /// logger.print("+set number\n");
+    /// ```
+    pub fn print(&mut self, text: &str) {
+        write!(self.target, "{}", self.mask(text)).unwrap();
     }
 
     /// Writes a message to the target with the label `INFO` and the current timestamp.
@@ -69,7 +321,7 @@ where
     ///
     /// Basic usage:
     /// ```
-    /// use logger::Logger;
+    /// use dotman::logger::Logger;
     ///
     /// let mut logger = Logger::new();
     /// logger.info("This is an info message.");
@@ -80,7 +332,7 @@ where
             "{} [{}]: {}",
             Color::Blue.bold().paint("INFO"),
             Style::new().bold().paint(get_fmt_time()),
-            msg,
+            self.mask(msg),
         )
         .unwrap();
     }
@@ -95,7 +347,7 @@ where
     ///
     /// Basic usage:
     /// ```
-    /// use logger::Logger;
+    /// use dotman::logger::Logger;
     ///
     /// let mut logger = Logger::new();
     /// logger.success("This is a success message.");
@@ -106,7 +358,7 @@ where
             "{} [{}]: {}",
             Color::Green.bold().paint("SUCCESS"),
             Style::new().bold().paint(get_fmt_time()),
-            msg,
+            self.mask(msg),
         )
         .unwrap();
     }
@@ -121,7 +373,7 @@ where
     ///
     /// Basic usage:
     /// ```
-    /// use logger::Logger;
+    /// use dotman::logger::Logger;
     ///
     /// let mut logger = Logger::new();
     /// logger.warn("This is a warning message.");
@@ -132,7 +384,7 @@ where
             "{} [{}]: {}",
             Color::Yellow.bold().paint("WARNING"),
             Style::new().bold().paint(get_fmt_time()),
-            msg,
+            self.mask(msg),
         )
         .unwrap();
     }
@@ -147,7 +399,7 @@ where
     ///
     /// Basic usage:
     /// ```
-    /// use logger::Logger;
+    /// use dotman::logger::Logger;
     ///
     /// let mut logger = Logger::new();
     /// logger.error("This is an error message.");
@@ -158,13 +410,15 @@ where
             "{} [{}]: {}",
             Color::Red.bold().paint("ERROR"),
             Style::new().bold().paint(get_fmt_time()),
-            msg,
+            self.mask(msg),
         )
         .unwrap();
     }
 
     /// Writes a message to the target with the label `FATAL` and the current timestamp.
-    /// Also terminates the program with exit code 1.
+    ///
+    /// The program is not terminated; that is left to the caller (e.g. the
+    /// binary exits with code 1), so that library code never exits.
     ///
     /// # Arguments
     ///
@@ -174,37 +428,53 @@ where
     ///
     /// Basic usage:
     /// ```
-    /// use logger::Logger;
+    /// use dotman::logger::Logger;
     ///
     /// let mut logger = Logger::new();
     /// logger.fatal("This is a fatal error message.");
     /// ```
+    ///
+    /// Secrets are masked at this level too:
+    /// ```
+    /// use dotman::logger::Logger;
+    ///
+    /// let mut logger = Logger::from(Vec::new());
+    /// logger.add_secret("hunter2");
+    ///
+    /// logger.fatal("Failed to log in with password hunter2");
+    /// let out = String::from_utf8_lossy(logger.target());
+    /// assert!(out.contains("FATAL"));
+    /// assert!(out.ends_with(": Failed to log in with password ****\n"));
+    /// ```
     pub fn fatal(&mut self, msg: &str) {
         writeln!(
             self.target,
             "{} [{}]: {}",
             Color::Red.bold().paint("FATAL"),
             Style::new().bold().paint(get_fmt_time()),
-            msg,
+            self.mask(msg),
         )
         .unwrap();
-
-        process::exit(1);
     }
 }
 
 impl Logger<Stdout> {
-    /// Creates a new logger that writes to stdout.
+    /// Creates a new logger that writes to stdout and reads answers from stdin.
     ///
     /// # Examples
     ///
     /// Creating a new logger that writes to stdout:
     /// ```
-    /// use logger::Logger;
+    /// use dotman::logger::Logger;
     ///
     /// let mut logger = Logger::new();
     /// ```
     pub fn new() -> Self {
-        Self::from(io::stdout())
+        let mut logger = Self::from(io::stdout());
+
+        // NOTE: Stdin is already buffered. A buffer of one byte keeps anything
+        // after an answer there, for whatever else reads from stdin.
+        logger.set_input(io::BufReader::with_capacity(1, io::stdin()));
+        logger
     }
 }
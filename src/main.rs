@@ -1,76 +1,955 @@
-pub mod config;
-pub mod consts;
-pub mod display;
-pub mod dotfiles;
-pub mod logger;
-pub mod shell;
-
-use logger::Logger;
+use ansi_term::{ANSIString, Color};
+use dotman::config::{self, Config, Context, Format, Registry, Stats};
+use dotman::consts::{ENCRYPTED_EXTENSION, ENCRYPTED_VALUE_KEY};
+use dotman::dotfiles::link::{self, Linker, Strategy};
+use dotman::dotfiles::manifest::Manifest;
+use dotman::dotfiles::status::State;
+use dotman::dotfiles::{self, git, status};
+use dotman::logger::Logger;
+use dotman::{crypt, display, template};
+use git2::Repository;
+use serde_json::json;
 use shellexpand::tilde;
 use std::env;
-use std::path::PathBuf;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 use structopt::StructOpt;
 
 /// > Help message goes here. <
 // TODO: Improve the flag names(?).
 #[derive(StructOpt)]
-struct Flags {
-    #[structopt(short = "r", long = "repository")]
-    repo: String,
+enum Command {
+    /// Clones a dotfiles repository, overwriting any existing local copy.
+    Clone {
+        #[structopt(short = "r", long = "repository")]
+        repo: String,
 
-    #[structopt(short = "d", long = "dir")]
-    dest: Option<PathBuf>,
+        #[structopt(short = "d", long = "dir")]
+        dest: Option<PathBuf>,
+    },
+
+    /// Runs the tasks defined in a dotfiles repository's config.
+    ///
+    /// The repository is cloned first if it does not exist locally.
+    Apply {
+        #[structopt(short = "r", long = "repository")]
+        repo: Option<String>,
+
+        #[structopt(short = "d", long = "dir")]
+        dest: Option<PathBuf>,
+
+        #[structopt(short = "c", long = "config")]
+        config: Option<PathBuf>,
+
+        /// The format of the config file (json, yaml or toml). By default, it
+        /// is determined from the file's extension.
+        #[structopt(long = "format")]
+        format: Option<Format>,
+
+        /// Overwrite existing files when deploying dotfiles, unless a task
+        /// specifies otherwise.
+        #[structopt(short = "f", long = "force")]
+        force: bool,
+
+        /// Don't remove deployed dotfiles whose source was removed from the
+        /// repository.
+        #[structopt(long = "no-prune")]
+        no_prune: bool,
+
+        /// Show a diff before replacing copied or templated files.
+        #[structopt(long = "diff")]
+        diff: bool,
+    },
+
+    /// Shows how applying the config would change copied or templated
+    /// dotfiles.
+    Diff {
+        #[structopt(short = "d", long = "dir")]
+        dest: Option<PathBuf>,
+
+        #[structopt(short = "c", long = "config")]
+        config: Option<PathBuf>,
+
+        /// The format of the config file (json, yaml or toml). By default, it
+        /// is determined from the file's extension.
+        #[structopt(long = "format")]
+        format: Option<Format>,
+    },
+
+    /// Checks the config for problems (e.g. unknown modules, invalid task
+    /// arguments or missing scripts) without running any tasks.
+    Check {
+        #[structopt(short = "d", long = "dir")]
+        dest: Option<PathBuf>,
+
+        #[structopt(short = "c", long = "config")]
+        config: Option<PathBuf>,
+
+        /// The format of the config file (json, yaml or toml). By default, it
+        /// is determined from the file's extension.
+        #[structopt(long = "format")]
+        format: Option<Format>,
+    },
+
+    /// Prints the JSON Schema of the config file, for editors to validate and
+    /// autocomplete configs with.
+    Schema,
+
+    /// Moves existing files into the dotfiles repository and links them back.
+    ///
+    /// Files are moved to the same location relative to the repository (or
+    /// package) as they have relative to the target directory, and staged so
+    /// that they can be committed.
+    Adopt {
+        #[structopt(short = "d", long = "dir")]
+        dest: Option<PathBuf>,
+
+        /// The package (a directory in the repository) to move the files into.
+        #[structopt(short = "p", long = "package")]
+        package: Option<PathBuf>,
+
+        /// The directory that the files are deployed to.
+        #[structopt(short = "t", long = "target", default_value = "~")]
+        target: PathBuf,
+
+        /// The files or directories to adopt (e.g. `~/.config/nvim`).
+        #[structopt(required = true)]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Encrypts secret files so that they can be committed.
+    ///
+    /// Each file is encrypted to a file with the `.age` extension added, which
+    /// is decrypted when it is deployed. A key is generated first if there
+    /// isn't one yet.
+    Encrypt {
+        /// Keep the unencrypted files.
+        #[structopt(short = "k", long = "keep")]
+        keep: bool,
+
+        /// Encrypt a string instead, printing an encrypted value that can be
+        /// used in the config.
+        #[structopt(short = "s", long = "string")]
+        string: Option<String>,
+
+        #[structopt(required_unless = "string")]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Decrypts encrypted files (e.g. to edit them) next to the originals.
+    ///
+    /// In a repository, the decrypted files are added to the `.gitignore` and
+    /// `.dotmanignore` at its root, so that they are neither committed nor
+    /// deployed. Files that the repository tracks are never decrypted over.
+    Decrypt {
+        #[structopt(required = true)]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Removes all deployed dotfiles and restores the files they replaced.
+    Unlink,
+
+    /// Pulls the latest changes into a local dotfiles repository.
+    ///
+    /// The repository is cloned first if it does not exist locally.
+    Sync {
+        #[structopt(short = "r", long = "repository")]
+        repo: Option<String>,
+
+        #[structopt(short = "d", long = "dir")]
+        dest: Option<PathBuf>,
+    },
+
+    /// Shows how the deployed dotfiles and the local repository differ from
+    /// what was last applied.
+    Status {
+        #[structopt(short = "d", long = "dir")]
+        dest: Option<PathBuf>,
+
+        /// Print the status as JSON.
+        #[structopt(long = "json")]
+        json: bool,
+
+        /// Fetch from the remote first, so that the number of commits behind
+        /// is up to date.
+        #[structopt(long = "fetch")]
+        fetch: bool,
+    },
 }
 
-fn main() {
-    display::print_logo();
+/// Writes a fatal error message and exits with code 1.
+fn fatal<W: Write>(logger: &mut Logger<W>, msg: &str) -> ! {
+    logger.fatal(msg);
+    process::exit(1);
+}
 
-    // Parse command line flags and create a logger.
-    let flags = Flags::from_args();
-    let mut logger = Logger::new();
+/// Expands `~` in a path if it exists.
+fn expand(path: PathBuf) -> PathBuf {
+    let path_str = path.display().to_string();
 
-    // If the user did not specify a destination for the cloned dotfiles,
-    // we use the current working directory.
-    let dest = match flags.dest {
-        Some(d) => {
-            let d_str = d.display().to_string();
+    if path_str.starts_with("~") {
+        PathBuf::from(format!("{}", tilde(&path_str)))
+    } else {
+        path
+    }
+}
 
-            // Expand `~` if exists in path.
-            if d_str.starts_with("~") {
-                PathBuf::from(format!("{}", tilde(&d_str)))
-            } else {
-                d
-            }
-        }
+/// Returns the local directory for the dotfiles.
+///
+/// If the user did not specify a destination for the cloned dotfiles, we use
+/// the current working directory.
+fn destination<W: Write>(dest: Option<PathBuf>, logger: &mut Logger<W>) -> PathBuf {
+    match dest {
+        Some(d) => expand(d),
 
         None => {
             if let Ok(d) = env::current_dir() {
                 d
             } else {
                 // If current dir couldn't be determined, fail with fatal error.
-                logger.fatal("Could not determine the current working directory.");
-
-                // NOTE: The program terminates with the previous statement.
-                // The following macro prevents the compiler from complaining
-                // about match arms having incompatible types.
-                unreachable!();
+                fatal(logger, "Could not determine the current working directory.");
             }
         }
-    };
+    }
+}
 
-    display::banner("TASK: Clone dotfiles.", None, None);
+/// Clones the dotfiles repository to the destination directory.
+///
+/// If `force` is `false` and the destination already exists, the existing
+/// repository is opened instead.
+fn clone<W: Write>(repo: &str, dest: &PathBuf, force: bool, logger: &mut Logger<W>) -> Repository {
+    logger.print(&display::banner("TASK: Clone dotfiles.", None, None));
     // TODO: Improve all the following logging messages.
     logger.info(&format!("Cloning dotfiles to {}", &dest.display()));
 
-    match dotfiles::clone(&flags.repo, &dest, true) {
-        Ok(_) => logger.success(&format!(
-            "Successfully cloned dotfiles to {}",
-            dest.display(),
-        )),
+    match dotfiles::clone(repo, dest, force) {
+        Ok(r) => {
+            logger.success(&format!(
+                "Successfully cloned dotfiles to {}",
+                dest.display(),
+            ));
+            println!();
+            r
+        }
         // TODO: Some error messages are not very indicative of what actually went wrong.
         // Not yet sure of what I can do to fix this, considering the error message
         // string is provided directly by the git2 library.
         // ? Maybe a `match` on the ErrorKind?
-        Err(e) => logger.fatal(&e.to_string()),
+        Err(e) => fatal(logger, &e.to_string()),
+    }
+}
+
+/// Opens the dotfiles repository in the destination directory, cloning it
+/// first if it does not exist locally.
+fn open_or_clone<W: Write>(
+    repo: Option<&str>,
+    dest: &PathBuf,
+    logger: &mut Logger<W>,
+) -> Repository {
+    if dest.exists() {
+        return match dotfiles::open(dest) {
+            Ok(r) => r,
+            Err(e) => fatal(logger, &e.to_string()),
+        };
+    }
+
+    match repo {
+        Some(repo) => clone(repo, dest, false, logger),
+        None => fatal(
+            logger,
+            &format!(
+                "No dotfiles found at {} and no repository specified to clone from.",
+                dest.display()
+            ),
+        ),
+    }
+}
+
+/// Removes deployed dotfiles whose source no longer exists in the repository.
+fn prune<W: Write>(root: &Path, logger: &mut Logger<W>) {
+    let mut manifest = match Manifest::load() {
+        Ok(m) => m,
+        Err(e) => {
+            logger.error(&format!("Failed to load the deployment manifest: {}", e));
+            return;
+        }
+    };
+
+    match link::prune(&mut manifest, root, logger) {
+        Ok(0) => (),
+        Ok(n) => logger.success(&format!("Pruned {} orphaned dotfile(s).", n)),
+        Err(e) => logger.error(&format!("Failed to prune orphaned dotfiles: {}", e)),
+    }
+
+    // Entries pruned before a failure have to be forgotten either way.
+    if let Err(e) = manifest.save() {
+        logger.error(&format!("Failed to save the deployment manifest: {}", e));
+    }
+}
+
+/// Returns the root directory of the dotfiles repository.
+fn workdir<W: Write>(repo: &Repository, logger: &mut Logger<W>) -> PathBuf {
+    match repo.workdir() {
+        Some(root) => root.to_path_buf(),
+        None => fatal(logger, "Bare repositories are not supported."),
+    }
+}
+
+/// Returns the config file to use for the dotfiles repository.
+///
+/// An explicitly specified config takes precedence over the one found in the
+/// repository.
+fn config_file<W: Write>(root: &Path, config: Option<PathBuf>, logger: &mut Logger<W>) -> PathBuf {
+    let file = match config {
+        Some(c) => expand(c),
+        None => match config::find(root) {
+            Some(f) => f,
+            None => fatal(
+                logger,
+                &format!("No config file found in {}", root.display()),
+            ),
+        },
+    };
+
+    if !file.is_file() {
+        fatal(
+            logger,
+            &format!("No config file found at {}", file.display()),
+        );
+    }
+
+    file
+}
+
+/// Parses the config file, exiting with an error if it is invalid.
+fn load_config<W: Write>(file: &Path, format: Option<Format>, logger: &mut Logger<W>) -> Config {
+    match config::parse(file, format) {
+        Ok(config) => {
+            redact(&config, logger);
+            config
+        }
+        Err(e) => {
+            logger.error(&e.to_string());
+            process::exit(1);
+        }
+    }
+}
+
+/// Makes sure that the logger never displays the secrets in the config.
+fn redact<W: Write>(config: &Config, logger: &mut Logger<W>) {
+    for secret in config.secrets.iter() {
+        logger.add_secret(secret);
+    }
+
+    for pattern in config.redact.iter() {
+        if let Err(e) = logger.add_pattern(pattern) {
+            fatal(
+                logger,
+                &format!("Invalid redaction pattern {}: {}", pattern, e),
+            );
+        }
+    }
+}
+
+/// Lists every problem with the tasks in the config and exits with an error.
+fn invalid<W: Write>(problems: &[String], logger: &mut Logger<W>) -> ! {
+    for problem in problems.iter() {
+        logger.error(problem);
+    }
+    fatal(
+        logger,
+        &format!(
+            "Found {} problem(s) in the config; nothing was changed.",
+            problems.len()
+        ),
+    );
+}
+
+/// Runs the tasks defined in the dotfiles config and prints the task stats.
+#[allow(clippy::too_many_arguments)]
+fn apply<W: Write>(
+    repo: Option<&str>,
+    dest: &PathBuf,
+    config: Option<PathBuf>,
+    format: Option<Format>,
+    force: bool,
+    no_prune: bool,
+    diff: bool,
+    logger: &mut Logger<W>,
+) {
+    let repo = open_or_clone(repo, dest, logger);
+    let root = workdir(&repo, logger);
+    let file = config_file(&root, config, logger);
+
+    logger.info(&format!("Using config file {}", file.display()));
+    println!();
+
+    let config = load_config(&file, format, logger);
+
+    let mut context = Context::new(&root, &config);
+    context.diff = diff;
+    if force {
+        context.conflict = Strategy::Overwrite;
+    }
+
+    // Nothing is changed if any task can't be run, including pruning.
+    let registry = Registry::default();
+    let plan = match dotman::plan(&config, &registry, &context) {
+        Ok(plan) => plan,
+        Err(problems) => invalid(&problems, logger),
+    };
+
+    if !no_prune {
+        prune(&root, logger);
+    }
+
+    let outcomes = dotman::apply(&plan, &registry, &context, logger);
+    let mut stats = Stats::new(&outcomes);
+
+    // The repository being ready counts as the first completed task.
+    stats.total_tasks += 1;
+    stats.ok_tasks += 1;
+
+    dotman::report(&stats, logger);
+}
+
+/// Prints the changes that applying the config would make to copied or
+/// templated dotfiles.
+fn diff<W: Write>(
+    dest: &PathBuf,
+    config: Option<PathBuf>,
+    format: Option<Format>,
+    logger: &mut Logger<W>,
+) {
+    let repo = match dotfiles::open(dest) {
+        Ok(r) => r,
+        Err(e) => fatal(logger, &e.to_string()),
+    };
+    let root = workdir(&repo, logger);
+    let file = config_file(&root, config, logger);
+    let config = load_config(&file, format, logger);
+
+    let context = Context::new(&root, &config);
+    let data = template::data(&context.facts, &context.variables);
+
+    let manifest = match Manifest::load() {
+        Ok(m) => m,
+        Err(e) => fatal(
+            logger,
+            &format!("Failed to load the deployment manifest: {}", e),
+        ),
+    };
+
+    let mut diffs = String::new();
+    for entry in manifest.entries.iter() {
+        if !entry.source.starts_with(&root) {
+            continue;
+        }
+
+        match status::pending(entry, &data) {
+            Ok(Some(diff)) => diffs.push_str(&diff),
+            Ok(None) => (),
+            Err(e) => logger.error(&format!(
+                "Failed to compare {}: {}",
+                entry.target.display(),
+                e
+            )),
+        }
+    }
+
+    if diffs.is_empty() {
+        logger.success("No changes to apply.");
+    } else {
+        // Rendered templates may contain decrypted values.
+        display::page(&logger.mask(&diffs));
+    }
+}
+
+/// Checks the config for problems without changing anything.
+fn check<W: Write>(
+    dest: &PathBuf,
+    config: Option<PathBuf>,
+    format: Option<Format>,
+    logger: &mut Logger<W>,
+) {
+    let repo = match dotfiles::open(dest) {
+        Ok(r) => r,
+        Err(e) => fatal(logger, &e.to_string()),
+    };
+    let root = workdir(&repo, logger);
+    let file = config_file(&root, config, logger);
+
+    logger.info(&format!("Checking config file {}", file.display()));
+    let config = load_config(&file, format, logger);
+    let context = Context::new(&root, &config);
+
+    // NOTE: Only problems are looked for, since finding out which tasks have
+    // something to do may run commands (e.g. `unless` guards).
+    let registry: Registry<W> = Registry::default();
+    let problems = config.check(&registry, &context);
+    if !problems.is_empty() {
+        invalid(&problems, logger);
+    }
+
+    logger.success(&format!(
+        "No problems found in {} task(s).",
+        config.tasks().len()
+    ));
+}
+
+/// Prints the JSON Schema of the config file.
+fn schema<W: Write>(logger: &mut Logger<W>) {
+    let registry: Registry<W> = Registry::default();
+    match serde_json::to_string_pretty(&config::schema(&registry)) {
+        Ok(schema) => println!("{}", schema),
+        Err(e) => fatal(logger, &format!("Failed to generate the schema: {}", e)),
+    }
+}
+
+/// Moves existing files into the dotfiles repository, links them back and
+/// stages them.
+fn adopt<W: Write>(
+    dest: &PathBuf,
+    package: Option<PathBuf>,
+    target: PathBuf,
+    paths: Vec<PathBuf>,
+    logger: &mut Logger<W>,
+) {
+    let repo = match dotfiles::open(dest) {
+        Ok(r) => r,
+        Err(e) => fatal(logger, &e.to_string()),
+    };
+    let root = workdir(&repo, logger);
+    let package = match package {
+        Some(p) => root.join(p),
+        None => root.clone(),
+    };
+
+    let manifest = match Manifest::load() {
+        Ok(m) => m,
+        Err(e) => fatal(
+            logger,
+            &format!("Failed to load the deployment manifest: {}", e),
+        ),
+    };
+    let mut linker = Linker::new(&root, &expand(target), manifest);
+
+    logger.print(&display::banner("TASK: Adopt dotfiles.", None, None));
+
+    let mut adopted = Vec::new();
+    let mut failed = 0;
+    for path in paths.into_iter() {
+        // Relative paths are relative to where dotman was run from.
+        let path = expand(path);
+        let path = match env::current_dir() {
+            Ok(cwd) => cwd.join(path),
+            Err(_) => path,
+        };
+
+        match linker.adopt(&package, &path, logger) {
+            Ok(src) => adopted.push(src),
+            Err(e) => {
+                logger.error(&format!("Failed to adopt {}: {}", path.display(), e));
+                failed += 1;
+            }
+        }
+    }
+
+    if let Err(e) = linker.manifest.save() {
+        logger.error(&format!("Failed to save the deployment manifest: {}", e));
+    }
+
+    if !adopted.is_empty() {
+        match git::stage(&repo, &adopted) {
+            Ok(()) => logger.success(&format!(
+                "Adopted {} file(s). Commit them to keep them in the repository.",
+                adopted.len()
+            )),
+            Err(e) => logger.error(&format!("Failed to stage adopted files: {}", e)),
+        }
+    }
+
+    if failed > 0 {
+        fatal(logger, &format!("Failed to adopt {} file(s).", failed));
+    }
+}
+
+/// Encrypts secret files with the key, generating one if necessary.
+fn encrypt<W: Write>(
+    paths: Vec<PathBuf>,
+    keep: bool,
+    string: Option<String>,
+    logger: &mut Logger<W>,
+) {
+    let key_file = crypt::key_file();
+    let key = if key_file.exists() {
+        crypt::load_key(&key_file)
+    } else {
+        let key = crypt::generate_key(&key_file);
+        if key.is_ok() {
+            logger.warn(&format!(
+                "Generated a new key at {}. Back it up: encrypted files cannot be decrypted without it.",
+                key_file.display()
+            ));
+        }
+        key
+    };
+
+    let recipient = match key {
+        Ok(key) => key.to_public(),
+        Err(e) => fatal(logger, &format!("Failed to load the key: {}", e)),
+    };
+
+    if let Some(string) = string {
+        match crypt::encrypt(&recipient, string.as_bytes()) {
+            Ok(encrypted) => println!("{}", json!({ ENCRYPTED_VALUE_KEY: encrypted })),
+            Err(e) => fatal(logger, &format!("Failed to encrypt the string: {}", e)),
+        }
+    }
+
+    let mut failed = 0;
+    for path in paths.into_iter().map(expand) {
+        let result = crypt::encrypt_file(&recipient, &path).and_then(|encrypted| {
+            if !keep {
+                fs::remove_file(&path)?;
+            }
+            Ok(encrypted)
+        });
+
+        match result {
+            Ok(encrypted) => logger.success(&format!(
+                "Encrypted {} to {}",
+                path.display(),
+                encrypted.display()
+            )),
+            Err(e) => {
+                logger.error(&format!("Failed to encrypt {}: {}", path.display(), e));
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        fatal(logger, &format!("Failed to encrypt {} file(s).", failed));
+    }
+}
+
+/// Makes sure that the decrypted copy of an encrypted file in a repository is
+/// neither committed nor deployed (it would be deployed to the same place as
+/// the encrypted file).
+fn exclude(decrypted: &Path) -> Result<(), String> {
+    let dir = decrypted.parent().unwrap_or_else(|| Path::new("."));
+    let repo = match Repository::discover(dir) {
+        Ok(repo) => repo,
+        Err(_) => return Ok(()),
+    };
+    let root = match repo.workdir() {
+        Some(root) => root.to_path_buf(),
+        None => return Ok(()),
+    };
+
+    // NOTE: The repository's paths are canonical, so the path has to be too.
+    let decrypted = fs::canonicalize(dir)
+        .map_err(|e| e.to_string())?
+        .join(decrypted.file_name().unwrap_or_default());
+
+    git::exclude(&repo, &decrypted)
+        .map_err(|e| format!("{}. Remove it from the repository first.", e.message()))?;
+    dotfiles::ignore::exclude(&root, &decrypted).map_err(|e| e.to_string())
+}
+
+/// Decrypts encrypted files next to the originals, making sure that the
+/// decrypted files are neither committed nor deployed.
+fn decrypt<W: Write>(paths: Vec<PathBuf>, logger: &mut Logger<W>) {
+    let mut failed = 0;
+    for path in paths.into_iter().map(expand) {
+        // NOTE: Other files are rejected by `crypt::decrypt_to_file`.
+        if path.extension().is_some_and(|e| e == ENCRYPTED_EXTENSION) {
+            if let Err(e) = exclude(&path.with_extension("")) {
+                logger.error(&format!("Refusing to decrypt {}: {}", path.display(), e));
+                failed += 1;
+                continue;
+            }
+        }
+
+        match crypt::decrypt_to_file(&path) {
+            Ok(decrypted) => logger.success(&format!(
+                "Decrypted {} to {}",
+                path.display(),
+                decrypted.display()
+            )),
+            Err(e) => {
+                logger.error(&format!("Failed to decrypt {}: {}", path.display(), e));
+                failed += 1;
+            }
+        }
+    }
+
+    logger.warn("Decrypted files must not be committed. Encrypt them again after editing.");
+
+    if failed > 0 {
+        fatal(logger, &format!("Failed to decrypt {} file(s).", failed));
+    }
+}
+
+/// Removes everything recorded in the deployment manifest.
+fn unlink<W: Write>(logger: &mut Logger<W>) {
+    let mut manifest = match Manifest::load() {
+        Ok(m) => m,
+        Err(e) => fatal(
+            logger,
+            &format!("Failed to load the deployment manifest: {}", e),
+        ),
+    };
+
+    logger.print(&display::banner("TASK: Unlink dotfiles.", None, None));
+
+    // Entries are undone in reverse order so that links are removed before the
+    // directories that hold them.
+    let mut failed = Vec::new();
+    while let Some(entry) = manifest.entries.pop() {
+        if let Err(e) = link::unlink(&entry, logger) {
+            logger.error(&format!(
+                "Failed to remove {}: {}",
+                entry.target.display(),
+                e
+            ));
+            failed.insert(0, entry);
+        }
+    }
+
+    // Keep track of anything we failed to remove so that it can be retried.
+    manifest.entries = failed;
+    if let Err(e) = manifest.save() {
+        fatal(
+            logger,
+            &format!("Failed to save the deployment manifest: {}", e),
+        );
+    }
+
+    if manifest.entries.is_empty() {
+        logger.success("Successfully removed all deployed dotfiles.");
+    } else {
+        fatal(
+            logger,
+            &format!(
+                "Failed to remove {} deployed dotfile(s).",
+                manifest.entries.len()
+            ),
+        );
+    }
+}
+
+/// Pulls the latest changes into the local dotfiles repository.
+fn sync<W: Write>(repo: Option<&str>, dest: &PathBuf, logger: &mut Logger<W>) {
+    let repo = open_or_clone(repo, dest, logger);
+
+    logger.print(&display::banner("TASK: Sync dotfiles.", None, None));
+    logger.info(&format!("Pulling latest changes into {}", dest.display()));
+
+    match dotfiles::sync(&repo) {
+        Ok(true) => logger.success("Successfully pulled latest changes."),
+        Ok(false) => logger.success("Dotfiles are already up to date."),
+        Err(e) => fatal(logger, &e.to_string()),
+    }
+}
+
+/// Returns the color that a dotfile state is displayed in.
+fn state_color(state: State) -> Color {
+    match state {
+        State::Ok => Color::Green,
+        State::Missing | State::Orphaned => Color::Red,
+        State::Replaced | State::Modified => Color::Yellow,
+        State::Outdated => Color::Blue,
+    }
+}
+
+/// Prints how the deployed dotfiles and the local repository have drifted.
+fn status<W: Write>(dest: &PathBuf, json: bool, fetch: bool, logger: &mut Logger<W>) {
+    let repo = match dotfiles::open(dest) {
+        Ok(r) => r,
+        Err(e) => fatal(logger, &e.to_string()),
+    };
+
+    if fetch {
+        if let Err(e) = git::fetch(&repo, "origin") {
+            logger.warn(&format!("Failed to fetch from the remote: {}", e));
+        }
+    }
+
+    let manifest = match Manifest::load() {
+        Ok(m) => m,
+        Err(e) => fatal(
+            logger,
+            &format!("Failed to load the deployment manifest: {}", e),
+        ),
+    };
+
+    let status = match status::status(&repo, &manifest) {
+        Ok(s) => s,
+        Err(e) => fatal(logger, &e.to_string()),
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&status).unwrap());
+        return;
+    }
+
+    // Only dotfiles that have drifted are worth listing.
+    let rows: Vec<Vec<ANSIString>> = status
+        .dotfiles
+        .iter()
+        .filter(|d| d.state != State::Ok)
+        .map(|d| {
+            vec![
+                state_color(d.state)
+                    .bold()
+                    .paint(format!("{:?}", d.state).to_lowercase()),
+                d.target.display().to_string().into(),
+                d.source.display().to_string().into(),
+            ]
+        })
+        .collect();
+
+    logger.print(&display::banner("Dotfiles", Some('-'), None));
+    if rows.is_empty() {
+        logger.success(&format!(
+            "All {} deployed dotfile(s) are up to date.",
+            status.dotfiles.len()
+        ));
+        println!();
+    } else {
+        display::table(&["STATE", "TARGET", "SOURCE"], &rows);
+    }
+
+    let repository = &status.repository;
+    logger.print(&display::banner("Repository", Some('-'), None));
+
+    if repository.changes.is_empty() {
+        logger.success("No uncommitted changes.");
+    } else {
+        let rows: Vec<Vec<ANSIString>> = repository
+            .changes
+            .iter()
+            .map(|c| vec![Color::Yellow.bold().paint(c.change), c.path.clone().into()])
+            .collect();
+
+        display::table(&["CHANGE", "PATH"], &rows);
+    }
+
+    match (repository.ahead, repository.behind) {
+        (Some(0), Some(0)) => logger.success("Up to date with the remote."),
+        (Some(ahead), Some(behind)) => logger.warn(&format!(
+            "{} commit(s) ahead of and {} commit(s) behind the remote.",
+            ahead, behind
+        )),
+        _ => logger.info("The current branch does not track a remote branch."),
+    }
+}
+
+fn main() {
+    // Parse command line flags and create a logger.
+    let command = Command::from_args();
+    let mut logger = Logger::new();
+
+    // The logo would get in the way of machine-readable output.
+    if !matches!(
+        command,
+        Command::Status { json: true, .. }
+            | Command::Diff { .. }
+            | Command::Schema
+            | Command::Encrypt {
+                string: Some(_),
+                ..
+            }
+    ) {
+        display::print_logo();
+    }
+
+    match command {
+        Command::Clone { repo, dest } => {
+            let dest = destination(dest, &mut logger);
+            clone(&repo, &dest, true, &mut logger);
+        }
+
+        Command::Apply {
+            repo,
+            dest,
+            config,
+            format,
+            force,
+            no_prune,
+            diff,
+        } => {
+            let dest = destination(dest, &mut logger);
+            apply(
+                repo.as_deref(),
+                &dest,
+                config,
+                format,
+                force,
+                no_prune,
+                diff,
+                &mut logger,
+            );
+        }
+
+        Command::Diff {
+            dest,
+            config,
+            format,
+        } => {
+            let dest = destination(dest, &mut logger);
+            diff(&dest, config, format, &mut logger);
+        }
+
+        Command::Check {
+            dest,
+            config,
+            format,
+        } => {
+            let dest = destination(dest, &mut logger);
+            check(&dest, config, format, &mut logger);
+        }
+
+        Command::Schema => schema(&mut logger),
+
+        Command::Adopt {
+            dest,
+            package,
+            target,
+            paths,
+        } => {
+            let dest = destination(dest, &mut logger);
+            adopt(&dest, package, target, paths, &mut logger);
+        }
+
+        Command::Encrypt {
+            keep,
+            string,
+            paths,
+        } => encrypt(paths, keep, string, &mut logger),
+
+        Command::Decrypt { paths } => decrypt(paths, &mut logger),
+
+        Command::Unlink => unlink(&mut logger),
+
+        Command::Sync { repo, dest } => {
+            let dest = destination(dest, &mut logger);
+            sync(repo.as_deref(), &dest, &mut logger);
+        }
+
+        Command::Status { dest, json, fetch } => {
+            let dest = destination(dest, &mut logger);
+
+            // Keep stdout clean for the JSON output.
+            if json {
+                status(&dest, json, fetch, &mut Logger::from(io::stderr()));
+            } else {
+                status(&dest, json, fetch, &mut logger);
+            }
+        }
     }
 }
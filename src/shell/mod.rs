@@ -35,6 +35,8 @@ fn command(cmd: &str, args: Option<&Vec<&str>>) -> Command {
 ///
 /// Run a command and panic on error:
 /// ```
+/// use dotman::shell;
+///
 /// shell::spawn("echo", Some(&vec!["Hello", "world"])).unwrap();
 /// ```
 pub fn spawn(cmd: &str, args: Option<&Vec<&str>>) -> io::Result<Child> {
@@ -56,6 +58,8 @@ pub fn spawn(cmd: &str, args: Option<&Vec<&str>>) -> io::Result<Child> {
 ///
 /// Run a command and panic on error:
 /// ```
+/// use dotman::shell;
+///
 /// shell::output("echo", Some(&vec!["Hello", "world"])).unwrap();
 /// ```
 pub fn output(cmd: &str, args: Option<&Vec<&str>>) -> io::Result<Output> {
@@ -75,18 +79,20 @@ pub fn output(cmd: &str, args: Option<&Vec<&str>>) -> io::Result<Output> {
 ///
 /// Run a command and panic on error:
 /// ```
+/// use dotman::shell;
+///
 /// shell::status("echo", Some(&vec!["Hello", "world"])).unwrap();
 /// ```
 pub fn status(cmd: &str, args: Option<&Vec<&str>>) -> io::Result<ExitStatus> {
     command(cmd, args).status()
 }
 
-/// Runs a command as a child process in the shell.
+/// Runs a command as a child process in the shell and waits for it to finish.
 ///
-/// This is a convenience function that's used to just run a command without
-/// having to care about the returned value (i.e. the returned `ExitStatus` is
-/// discarded). To have greater control, use either the `spawn()`, `output()`
-/// or the `status()` function instead.
+/// This is a convenience function that's used to run a command from a single
+/// string, with the command's output going straight to the terminal. To have
+/// greater control, use either the `spawn()`, `output()` or the `status()`
+/// function instead.
 ///
 /// # Arguments
 ///
@@ -96,22 +102,20 @@ pub fn status(cmd: &str, args: Option<&Vec<&str>>) -> io::Result<ExitStatus> {
 ///
 /// Run a command in the shell:
 /// ```
-/// shell::run("echo Hello world");
+/// use dotman::shell;
+///
+/// assert!(shell::run("echo Hello world").unwrap().success());
 /// ```
-pub fn run(cmd: &str) {
+pub fn run(cmd: &str) -> io::Result<ExitStatus> {
     // Get list of arguments by splitting the command string on whitespace.
-    let args = cmd.split(char::is_whitespace).collect::<Vec<&str>>();
+    let args = cmd.split_whitespace().collect::<Vec<&str>>();
     // Separate the command from the arguments.
-    let cmd = args[0];
-    let args = Vec::from(&args[1..args.len()]);
+    let (cmd, args) = match args.split_first() {
+        Some((cmd, args)) => (*cmd, args.to_vec()),
+        None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty command")),
+    };
 
-    // If the arguments vector has only one element, then it's safe to assume
-    // that the command does not take any arguments.
-    if args.len() == 1 {
-        let _ = status(cmd, None);
-    }
-
-    let _ = status(cmd, Some(&args));
+    status(cmd, Some(&args))
 }
 
 // TODO: add option to write script output to a stream/file.
@@ -130,6 +134,8 @@ pub fn run(cmd: &str) {
 ///
 /// Running a script and printing a message based on success or failure:
 /// ```
+/// use dotman::shell;
+///
 /// let script = std::path::PathBuf::from("/usr/bin/hello.sh");
 ///
 /// if shell::run_script(&script) {
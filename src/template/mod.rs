@@ -0,0 +1,76 @@
+//! Module for rendering templated dotfiles.
+//!
+//! Templates use the Handlebars syntax. The variables defined in the config
+//! are available by name and the facts about the system under `facts`, e.g.:
+//!
+//! ```text
+//! [user]
+//!     name = {{ name }}
+//!     email = {{ facts.email }}
+//! {{#if (eq facts.os "linux")}}
+//! [credential]
+//!     helper = cache
+//! {{/if}}
+//! ```
+extern crate handlebars;
+extern crate serde_json;
+
+use crate::facts::Facts;
+use handlebars::{no_escape, Handlebars, TemplateRenderError};
+use serde_json::{Map, Value};
+use std::{fs, io, path::Path};
+
+/// Returns the data that templates are rendered with.
+///
+/// # Arguments
+///
+/// * `facts` - The facts about the current system.
+/// * `variables` - The variables defined in the config.
+pub fn data(facts: &Facts, variables: &Map<String, Value>) -> Value {
+    let mut data = variables.clone();
+    data.insert(String::from("facts"), serde_json::to_value(facts).unwrap());
+
+    Value::Object(data)
+}
+
+/// Renders a template string.
+///
+/// Rendering fails if the template refers to a variable that doesn't exist.
+///
+/// # Arguments
+///
+/// * `template` - The template.
+/// * `data` - The data to render the template with.
+///
+/// # Examples
+///
+/// ```
+/// use dotman::template;
+///
+/// let data = serde_json::json!({ "name": "John Doe" });
+/// let rendered = template::render("Hello {{ name }}!", &data).unwrap();
+///
+/// assert_eq!(rendered, "Hello John Doe!");
+/// ```
+pub fn render(template: &str, data: &Value) -> Result<String, Box<TemplateRenderError>> {
+    let mut handlebars = Handlebars::new();
+    handlebars.set_strict_mode(true);
+
+    // Dotfiles aren't HTML, so nothing should be escaped.
+    handlebars.register_escape_fn(no_escape);
+
+    handlebars.render_template(template, data).map_err(Box::new)
+}
+
+/// Renders a template file.
+///
+/// # Arguments
+///
+/// * `path` - The path to the template file.
+/// * `data` - The data to render the template with.
+pub fn render_file(path: &Path, data: &Value) -> io::Result<String> {
+    let template = fs::read_to_string(path)?;
+
+    render(&template, data)
+        .map_err(|e| io::Error::other(format!("Failed to render {}: {}", path.display(), e)))
+}